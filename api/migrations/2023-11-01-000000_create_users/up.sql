@@ -0,0 +1,7 @@
+-- databases made by hand before migrations existed already have this table
+CREATE TABLE IF NOT EXISTS users (
+    uuid VARCHAR PRIMARY KEY NOT NULL,
+    username VARCHAR NOT NULL,
+    password VARCHAR NOT NULL,
+    email VARCHAR NOT NULL
+);
@@ -0,0 +1,21 @@
+CREATE TABLE challenges (
+    uuid VARCHAR PRIMARY KEY NOT NULL,
+    quiz TEXT NOT NULL,
+    settings TEXT NOT NULL,
+    deadline BIGINT NOT NULL,
+    created BIGINT NOT NULL
+);
+
+CREATE TABLE attempts (
+    uuid VARCHAR PRIMARY KEY NOT NULL,
+    challenge VARCHAR NOT NULL REFERENCES challenges (uuid) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    question INTEGER NOT NULL DEFAULT 0,
+    served BIGINT,
+    points INTEGER NOT NULL DEFAULT 0,
+    correct INTEGER NOT NULL DEFAULT 0,
+    wrong INTEGER NOT NULL DEFAULT 0,
+    streak INTEGER NOT NULL DEFAULT 0,
+    finished BIGINT,
+    UNIQUE (challenge, name)
+);
@@ -0,0 +1,3 @@
+DROP TABLE answers;
+DROP TABLE questions;
+DROP TABLE quizzes;
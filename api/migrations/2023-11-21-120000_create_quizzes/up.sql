@@ -0,0 +1,27 @@
+CREATE TABLE quizzes (
+    uuid VARCHAR PRIMARY KEY NOT NULL,
+    name VARCHAR NOT NULL,
+    description TEXT NOT NULL
+);
+
+CREATE TABLE questions (
+    quiz VARCHAR NOT NULL REFERENCES quizzes (uuid) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    question TEXT NOT NULL,
+    body TEXT NOT NULL,
+    time_limit INTEGER NOT NULL,
+    points INTEGER NOT NULL,
+    double_points BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (quiz, position)
+);
+
+CREATE TABLE answers (
+    quiz VARCHAR NOT NULL,
+    question INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    option INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    correct BOOLEAN NOT NULL,
+    PRIMARY KEY (quiz, question, position),
+    FOREIGN KEY (quiz, question) REFERENCES questions (quiz, position) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+CREATE TABLE logins (
+    token VARCHAR PRIMARY KEY NOT NULL,
+    user VARCHAR NOT NULL REFERENCES users (uuid) ON DELETE CASCADE,
+    created BIGINT NOT NULL,
+    expires BIGINT NOT NULL
+);
+
+CREATE INDEX logins_user ON logins (user);
@@ -0,0 +1,5 @@
+DROP TABLE quiz_shares;
+
+ALTER TABLE quizzes DROP COLUMN owner;
+
+ALTER TABLE users DROP COLUMN role;
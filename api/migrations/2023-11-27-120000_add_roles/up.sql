@@ -0,0 +1,9 @@
+ALTER TABLE users ADD COLUMN role VARCHAR NOT NULL DEFAULT 'Player';
+
+ALTER TABLE quizzes ADD COLUMN owner VARCHAR REFERENCES users (uuid) ON DELETE SET NULL;
+
+CREATE TABLE quiz_shares (
+    quiz VARCHAR NOT NULL REFERENCES quizzes (uuid) ON DELETE CASCADE,
+    user VARCHAR NOT NULL REFERENCES users (uuid) ON DELETE CASCADE,
+    PRIMARY KEY (quiz, user)
+);
@@ -0,0 +1,10 @@
+-- accounts that existed before sign up had to be confirmed count as verified
+ALTER TABLE users ADD COLUMN verified BOOLEAN NOT NULL DEFAULT 1;
+
+CREATE TABLE verifications (
+    token VARCHAR PRIMARY KEY NOT NULL,
+    user VARCHAR NOT NULL REFERENCES users (uuid) ON DELETE CASCADE,
+    expires BIGINT NOT NULL
+);
+
+CREATE INDEX verifications_user ON verifications (user);
@@ -0,0 +1,7 @@
+CREATE TABLE password_resets (
+    token VARCHAR PRIMARY KEY NOT NULL,
+    user VARCHAR NOT NULL REFERENCES users (uuid) ON DELETE CASCADE,
+    expires BIGINT NOT NULL
+);
+
+CREATE INDEX password_resets_user ON password_resets (user);
@@ -0,0 +1,3 @@
+-- the migration runner refuses to get here while names or addresses clash, see db.rs
+CREATE UNIQUE INDEX users_username ON users (lower(username));
+CREATE UNIQUE INDEX users_email ON users (lower(email));
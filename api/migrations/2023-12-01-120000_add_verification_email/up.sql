@@ -0,0 +1,2 @@
+-- set when the link confirms a new address for an existing account rather than a sign up
+ALTER TABLE verifications ADD COLUMN email VARCHAR;
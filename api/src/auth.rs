@@ -0,0 +1,166 @@
+use actix_web::dev::Payload;
+use actix_web::{error, http::header, web, FromRequest, HttpRequest, HttpResponse};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::future::{ready, Ready};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::challenge;
+use crate::models::{Login, User};
+use crate::permissions::{self, Action};
+use crate::AppState;
+
+// a week in milliseconds, logins are unix milliseconds like challenges
+pub const LOGIN_LIFETIME: i64 = 7 * 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoginInfo {
+    pub token: String,
+    pub expires: i64,
+    pub user: User,
+}
+
+// tokens are 256 random bits so a fast hash is enough, a leaked table can't be replayed
+pub fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+impl Login {
+    // returns the token for the client next to the row to store
+    pub fn create(user: &User, now: i64) -> (String, Self) {
+        let token = new_token();
+
+        let login = Login {
+            token: token_hash(&token),
+            user: user.uuid.clone(),
+            created: now,
+            expires: now + LOGIN_LIFETIME,
+        };
+        (token, login)
+    }
+
+    pub fn is_valid(&self, now: i64) -> bool {
+        now < self.expires
+    }
+}
+
+fn bearer(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+// taking `Auth` as a handler argument makes a route require a login, the caller gets a 401
+// without a valid `Authorization: Bearer <token>` header
+#[derive(Debug, Clone)]
+pub struct Auth {
+    pub user: User,
+    pub token: String,
+}
+
+impl Auth {
+    pub fn can(&self, action: Action) -> bool {
+        permissions::allowed(&self.user, action)
+    }
+
+    // handlers return the error response as is, the same way as `find_challenge`
+    pub fn require(&self, action: Action) -> Result<(), HttpResponse> {
+        match self.can(action) {
+            true => Ok(()),
+            false => Err(HttpResponse::Forbidden().body("not allowed")),
+        }
+    }
+}
+
+impl FromRequest for Auth {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = match bearer(req) {
+            Some(token) => token,
+            None => return ready(Err(error::ErrorUnauthorized("missing login token"))),
+        };
+        let data = match req.app_data::<web::Data<AppState>>() {
+            Some(data) => data,
+            None => return ready(Err(error::ErrorInternalServerError("missing app state"))),
+        };
+
+        let hash = token_hash(token);
+        ready(match data.database.login_user(&hash, challenge::now()) {
+            Ok(Some(user)) => Ok(Auth { user, token: hash }),
+            Ok(None) => Err(error::ErrorUnauthorized("invalid or expired login token")),
+            Err(_) => Err(error::ErrorBadRequest(format!(
+                "failure in {} to check login",
+                data.app_name
+            ))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn user() -> User {
+        User {
+            uuid: "user".to_string(),
+            username: "Dave".to_string(),
+            password: String::new(),
+            email: "dave@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
+        }
+    }
+
+    #[test]
+    fn test_create_login() {
+        let (token, login) = Login::create(&user(), 1_000);
+        assert_eq!(token.len(), 43);
+        assert_eq!(login.token, token_hash(&token));
+        assert_ne!(login.token, token);
+        assert_eq!(login.user, "user");
+        assert!(login.is_valid(1_000 + LOGIN_LIFETIME - 1));
+        assert!(!login.is_valid(1_000 + LOGIN_LIFETIME));
+
+        let (other, _) = Login::create(&user(), 1_000);
+        assert_ne!(token, other);
+    }
+
+    #[test]
+    fn test_bearer() {
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer abc"))
+            .to_http_request();
+        assert_eq!(bearer(&req), Some("abc"));
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Basic abc"))
+            .to_http_request();
+        assert_eq!(bearer(&req), None);
+        assert_eq!(bearer(&TestRequest::default().to_http_request()), None);
+    }
+}
@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::game::Settings;
+use crate::models::{Attempt, Challenge, Player, Quiz};
+use crate::protocol::QuestionInfo;
+use crate::scoring::{self, AnswerResult, Submission};
+
+// challenge timestamps are unix milliseconds so they can be compared with Date.now() in the browser
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewChallenge {
+    pub quiz: Quiz,
+    #[serde(default)]
+    pub settings: Settings,
+    pub deadline: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChallengeInfo {
+    pub uuid: String,
+    pub name: String,
+    pub description: String,
+    pub questions: usize,
+    pub deadline: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AttemptInfo {
+    pub uuid: String,
+    pub challenge: String,
+    pub name: String,
+    pub question: Option<QuestionInfo>,
+    pub points: i32,
+    pub correct: i32,
+    pub wrong: i32,
+    pub streak: i32,
+    pub finished: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Answered {
+    pub result: AnswerResult,
+    pub attempt: AttemptInfo,
+}
+
+impl Challenge {
+    pub fn create(new: &NewChallenge, now: i64) -> Result<Self, String> {
+        if new.quiz.questions.is_empty() {
+            return Err("challenge has no questions".to_string());
+        }
+        new.quiz.validate()?;
+        if new.deadline <= now {
+            return Err("deadline must be in the future".to_string());
+        }
+        new.settings.validate()?;
+
+        Ok(Challenge {
+            uuid: Uuid::new_v4().to_string(),
+            quiz: serde_json::to_string(&new.quiz).map_err(|error| error.to_string())?,
+            settings: serde_json::to_string(&new.settings).map_err(|error| error.to_string())?,
+            deadline: new.deadline,
+            created: now,
+        })
+    }
+
+    // the quiz is stored as it was when the challenge was set, later edits don't move the goalposts
+    pub fn quiz(&self) -> Result<Quiz, String> {
+        serde_json::from_str(&self.quiz).map_err(|error| error.to_string())
+    }
+
+    pub fn settings(&self) -> Result<Settings, String> {
+        serde_json::from_str(&self.settings).map_err(|error| error.to_string())
+    }
+
+    pub fn is_open(&self, now: i64) -> bool {
+        now < self.deadline
+    }
+
+    pub fn info(&self, quiz: &Quiz) -> ChallengeInfo {
+        ChallengeInfo {
+            uuid: self.uuid.clone(),
+            name: quiz.name.clone(),
+            description: quiz.description.clone(),
+            questions: quiz.questions.len(),
+            deadline: self.deadline,
+        }
+    }
+}
+
+impl Attempt {
+    pub fn new(challenge: &Challenge, name: &str, now: i64) -> Self {
+        Attempt {
+            uuid: Uuid::new_v4().to_string(),
+            challenge: challenge.uuid.clone(),
+            name: name.to_string(),
+            question: 0,
+            served: Some(now),
+            points: 0,
+            correct: 0,
+            wrong: 0,
+            streak: 0,
+            finished: None,
+        }
+    }
+
+    pub fn player(&self) -> Player {
+        let mut player = Player::new(&self.name);
+        player.points = self.points;
+        player.correct = self.correct;
+        player.wrong = self.wrong;
+        player.streak = self.streak;
+        player
+    }
+
+    pub fn info(&self, quiz: &Quiz) -> AttemptInfo {
+        let index = self.question as usize;
+        let question = match self.finished {
+            Some(_) => None,
+            None => quiz
+                .questions
+                .get(index)
+                .map(|question| QuestionInfo::new(index, quiz.questions.len(), question)),
+        };
+
+        AttemptInfo {
+            uuid: self.uuid.clone(),
+            challenge: self.challenge.clone(),
+            name: self.name.clone(),
+            question,
+            points: self.points,
+            correct: self.correct,
+            wrong: self.wrong,
+            streak: self.streak,
+            finished: self.finished.is_some(),
+        }
+    }
+
+    // scored exactly like a live game, an answer after the time limit counts as no answer
+    pub fn answer(
+        &mut self,
+        quiz: &Quiz,
+        settings: &Settings,
+        question: usize,
+        option: i8,
+        now: i64,
+    ) -> Result<AnswerResult, String> {
+        if self.finished.is_some() {
+            return Err("attempt is already finished".to_string());
+        }
+        if question != self.question as usize {
+            return Err(format!("question {} is not open", question));
+        }
+        let current = match quiz.questions.get(question) {
+            Some(current) => current,
+            None => return Err(format!("question {} is not open", question)),
+        };
+        if !current.answers.iter().any(|answer| answer.option == option) {
+            return Err(format!("invalid option: {}", option));
+        }
+
+        let served = self.served.unwrap_or(now);
+        let elapsed = Duration::from_millis((now - served).max(0) as u64);
+        let mut answers = HashMap::new();
+        if elapsed <= Duration::from_secs(current.time_limit as u64) {
+            answers.insert(self.name.clone(), Submission { option, elapsed });
+        }
+
+        let mut players = [self.player()];
+        let mut results = scoring::score_question(current, settings, &mut players, &answers);
+        let [player] = players;
+        self.points = player.points;
+        self.correct = player.correct;
+        self.wrong = player.wrong;
+        self.streak = player.streak;
+
+        self.question += 1;
+        if self.question as usize >= quiz.questions.len() {
+            self.served = None;
+            self.finished = Some(now);
+        } else {
+            self.served = Some(now);
+        }
+        Ok(results.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Answer, Question};
+
+    fn quiz() -> Quiz {
+        let question = Question {
+            question: "Capital of France?".to_string(),
+            body: String::new(),
+            answers: vec![
+                Answer {
+                    option: 0,
+                    text: "Paris".to_string(),
+                    correct: true,
+                },
+                Answer {
+                    option: 1,
+                    text: "Lyon".to_string(),
+                    correct: false,
+                },
+            ],
+            time_limit: 20,
+            points: 1000,
+            double_points: false,
+        };
+        Quiz {
+            uuid: "quiz".to_string(),
+            name: "Capitals".to_string(),
+            description: String::new(),
+            questions: vec![question.clone(), question],
+        }
+    }
+
+    fn challenge() -> Challenge {
+        let new = NewChallenge {
+            quiz: quiz(),
+            settings: Settings::default(),
+            deadline: 10_000,
+        };
+        Challenge::create(&new, 0).unwrap()
+    }
+
+    #[test]
+    fn test_create_challenge() {
+        let challenge = challenge();
+        assert_eq!(challenge.quiz().unwrap(), quiz());
+        assert!(challenge.is_open(9_999));
+        assert!(!challenge.is_open(10_000));
+
+        let late = NewChallenge {
+            quiz: quiz(),
+            settings: Settings::default(),
+            deadline: 10,
+        };
+        assert!(Challenge::create(&late, 10).is_err());
+        let empty = NewChallenge {
+            quiz: Quiz::default(),
+            settings: Settings::default(),
+            deadline: 10_000,
+        };
+        assert!(Challenge::create(&empty, 0).is_err());
+
+        // a question without answers would leave every attempt stuck on it
+        let mut unanswerable = quiz();
+        unanswerable.questions[1].answers.clear();
+        let invalid = NewChallenge {
+            quiz: unanswerable,
+            settings: Settings::default(),
+            deadline: 10_000,
+        };
+        assert_eq!(
+            Challenge::create(&invalid, 0).unwrap_err(),
+            "question 2 needs at least two answers"
+        );
+    }
+
+    #[test]
+    fn test_attempt_scores_like_live_game() {
+        let challenge = challenge();
+        let settings = Settings::default();
+        let mut attempt = Attempt::new(&challenge, "Dave", 0);
+
+        assert!(attempt.answer(&quiz(), &settings, 1, 0, 0).is_err());
+        assert!(attempt.answer(&quiz(), &settings, 0, 7, 0).is_err());
+
+        let result = attempt.answer(&quiz(), &settings, 0, 0, 10_000).unwrap();
+        assert!(result.correct);
+        assert_eq!(result.points, 750);
+        assert_eq!(attempt.question, 1);
+        assert_eq!(attempt.served, Some(10_000));
+
+        let result = attempt.answer(&quiz(), &settings, 1, 0, 10_000).unwrap();
+        assert_eq!((result.streak, result.bonus, result.points), (2, 100, 1100));
+        assert_eq!(attempt.points, 1850);
+        assert_eq!(attempt.finished, Some(10_000));
+        assert!(attempt.info(&quiz()).question.is_none());
+        assert!(attempt.answer(&quiz(), &settings, 2, 0, 10_000).is_err());
+    }
+
+    #[test]
+    fn test_attempt_too_slow() {
+        let challenge = challenge();
+        let mut attempt = Attempt::new(&challenge, "Dave", 0);
+        let result = attempt
+            .answer(&quiz(), &Settings::default(), 0, 0, 21_000)
+            .unwrap();
+        assert!(!result.correct);
+        assert_eq!(result.option, None);
+        assert_eq!((attempt.points, attempt.wrong), (0, 1));
+    }
+}
@@ -1,28 +1,97 @@
-use crate::models::{Model, User};
+use crate::models::{
+    AnswerRow, Attempt, Challenge, Login, PasswordReset, QuestionRow, Quiz, QuizRow, User,
+    Verification,
+};
+use crate::password::{self, Verified};
+use crate::permissions::QuizAccess;
 use crate::schema::users::dsl::*;
+use crate::schema::{
+    answers, attempts, challenges, logins, password_resets, questions, quiz_shares, quizzes,
+    verifications,
+};
 use diesel::connection::SimpleConnection;
-use diesel::query_builder::QueryFragment;
+use diesel::migration::{MigrationSource, MigrationVersion};
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::Sqlite;
 use diesel::{
-    Insertable, OptionalExtension, QueryDsl, QueryableByName, RunQueryDsl, SelectableHelper,
-    SqliteConnection, Table,
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult,
+    RunQueryDsl, SelectableHelper, SqliteConnection,
 };
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use std::ptr::null;
 use std::{env, fmt};
 
+diesel::sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+const UNIQUE_USERS: &str = "20231130120000";
+
+#[derive(diesel::QueryableByName)]
+struct UserClash {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    field: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    value: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    uuids: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseErrorKind {
+    Other,
+    // a unique index was violated, holds the user facing name of the field
+    Conflict(&'static str),
+}
+
 pub struct DatabaseError {
     description: String,
+    kind: DatabaseErrorKind,
 }
 
 impl DatabaseError {
     pub fn new(description: &str) -> Self {
         DatabaseError {
             description: description.to_string(),
+            kind: DatabaseErrorKind::Other,
+        }
+    }
+
+    pub fn conflict(field: &'static str) -> Self {
+        DatabaseError {
+            description: format!("{} is already taken", field),
+            kind: DatabaseErrorKind::Conflict(field),
         }
     }
+
+    pub fn kind(&self) -> DatabaseErrorKind {
+        self.kind
+    }
+}
+
+// sqlite doesn't report constraint names to diesel, so the field is picked out of the message,
+// which names the column for plain constraints and the index for expression ones
+impl From<diesel::result::Error> for DatabaseError {
+    fn from(error: diesel::result::Error) -> Self {
+        if let diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            info,
+        ) = &error
+        {
+            let message = info.message();
+            for (field, names) in [
+                ("username", ["users.username", "users_username"]),
+                ("email", ["users.email", "users_email"]),
+                ("uuid", ["users.uuid", "users_uuid"]),
+            ] {
+                if names.iter().any(|name| message.contains(name)) {
+                    return DatabaseError::conflict(field);
+                }
+            }
+        }
+        DatabaseError::new(format!("Error: {}", error).as_str())
+    }
 }
 
 impl Display for DatabaseError {
@@ -51,10 +120,118 @@ impl Database {
         let pool = Pool::builder()
             .build(manager)
             .expect("Failed to create database connection pool");
-        Database { pool }
+        let database = Database { pool };
+
+        // tests never go through main, so the first database any of them opens brings the schema
+        // up to date before the others carry on
+        #[cfg(test)]
+        {
+            static MIGRATE: std::sync::Once = std::sync::Once::new();
+            MIGRATE.call_once(|| {
+                database
+                    .run_migrations()
+                    .expect("failed to migrate the test database");
+            });
+        }
+
+        database
+    }
+
+    // refuses to touch a database that has been migrated by a newer build, rolling it back
+    // would be the only way to make this binary's queries line up with it again
+    pub fn run_migrations(&self) -> Result<Vec<String>, DatabaseError> {
+        match self.pool.get() {
+            Ok(mut conn) => Self::migrate(&mut conn),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
     }
 
-    pub fn run_migrations(&self) {}
+    fn migrate(conn: &mut SqliteConnection) -> Result<Vec<String>, DatabaseError> {
+        let applied = conn
+            .applied_migrations()
+            .map_err(|error| DatabaseError::new(format!("Migration Error: {}", error).as_str()))?;
+        let known: Vec<MigrationVersion> = MigrationSource::<Sqlite>::migrations(&MIGRATIONS)
+            .map_err(|error| DatabaseError::new(format!("Migration Error: {}", error).as_str()))?
+            .iter()
+            .map(|migration| migration.name().version().as_owned())
+            .collect();
+        if let Some(unknown) = applied.iter().find(|version| !known.contains(version)) {
+            return Err(DatabaseError::new(
+                format!(
+                    "database schema is ahead of this build, unknown migration {}",
+                    unknown
+                )
+                .as_str(),
+            ));
+        }
+
+        // everything before the unique indexes runs first so there is a users table to check
+        let mut versions = Vec::new();
+        loop {
+            let pending = conn.pending_migrations(MIGRATIONS).map_err(|error| {
+                DatabaseError::new(format!("Migration Error: {}", error).as_str())
+            })?;
+            match pending.first() {
+                Some(next) if next.name().version().to_string() == UNIQUE_USERS => {
+                    Self::check_user_clashes(conn)?;
+                    break;
+                }
+                Some(_) => versions.push(
+                    conn.run_next_migration(MIGRATIONS)
+                        .map_err(|error| {
+                            DatabaseError::new(format!("Migration Error: {}", error).as_str())
+                        })?
+                        .to_string(),
+                ),
+                None => break,
+            }
+        }
+
+        conn.run_pending_migrations(MIGRATIONS)
+            .map(|applied| {
+                versions.extend(applied.iter().map(|version| version.to_string()));
+                versions
+            })
+            .map_err(|error| DatabaseError::new(format!("Migration Error: {}", error).as_str()))
+    }
+
+    // the unique indexes can't be built over duplicates, and which account should give up its
+    // name or address is for a person to decide, so the migration stops and says what clashes
+    fn check_user_clashes(conn: &mut SqliteConnection) -> Result<(), DatabaseError> {
+        let clashes: Vec<UserClash> = diesel::sql_query(
+            "SELECT 'username' AS field, lower(username) AS value, group_concat(uuid, ', ') AS uuids \
+             FROM (SELECT * FROM users ORDER BY uuid) GROUP BY lower(username) HAVING count(*) > 1 \
+             UNION ALL \
+             SELECT 'email', lower(email), group_concat(uuid, ', ') \
+             FROM (SELECT * FROM users ORDER BY uuid) GROUP BY lower(email) HAVING count(*) > 1",
+        )
+        .load(conn)
+        .map_err(|error| DatabaseError::new(format!("Migration Error: {}", error).as_str()))?;
+        if clashes.is_empty() {
+            return Ok(());
+        }
+
+        let clashes: Vec<String> = clashes
+            .iter()
+            .map(|clash| {
+                format!(
+                    "{} '{}' is used by {}",
+                    clash.field, clash.value, clash.uuids
+                )
+            })
+            .collect();
+        Err(DatabaseError::new(
+            format!(
+                "migration {} makes usernames and emails unique ignoring case, change these users \
+                 and start again: {}",
+                UNIQUE_USERS,
+                clashes.join("; ")
+            )
+            .as_str(),
+        ))
+    }
 
     pub fn health_check(&self) -> Result<(), DatabaseError> {
         let conn_result = self.pool.get();
@@ -64,7 +241,7 @@ impl Database {
                 .map(|_| ())
                 .map_err(|_e| DatabaseError::new("Batch execution error")),
             Err(r2d2_error) => Err(DatabaseError::new(
-                format!("r2d2 Error: {}", r2d2_error.to_string()).as_str(),
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
             )),
         }
     }
@@ -81,7 +258,7 @@ impl Database {
                 }
             }
             Err(r2d2_error) => Err(DatabaseError::new(
-                format!("r2d2 Error: {}", r2d2_error.to_string()).as_str(),
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
             )),
         }
     }
@@ -89,7 +266,7 @@ impl Database {
     pub fn user_list(&self, limit: u64) -> Result<Vec<User>, DatabaseError> {
         let mut limit: i64 = limit as i64;
         if limit == 0 {
-            limit = std::i64::MAX;
+            limit = i64::MAX;
         }
 
         let conn_result = self.pool.get();
@@ -100,7 +277,7 @@ impl Database {
                 .load(&mut conn)
                 .expect("error listing users")),
             Err(r2d2_error) => Err(DatabaseError::new(
-                format!("r2d2 Error: {}", r2d2_error.to_string()).as_str(),
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
             )),
         }
     }
@@ -113,12 +290,18 @@ impl Database {
                 .first::<User>(&mut conn)
                 .expect("Error finding user")),
             Err(r2d2_error) => Err(DatabaseError::new(
-                format!("r2d2 Error: {}", r2d2_error.to_string()).as_str(),
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
             )),
         }
     }
 
-    pub fn user_add(&self, user_model: User) -> Result<(), DatabaseError> {
+    pub fn user_add(&self, mut user_model: User) -> Result<(), DatabaseError> {
+        if user_model.password.is_empty() {
+            return Err(DatabaseError::new(
+                "Password Error: a new user needs a password",
+            ));
+        }
+        user_model.password = hash_password(&user_model.password)?;
         let conn_result = self.pool.get();
         match conn_result {
             Ok(mut conn) => {
@@ -127,57 +310,748 @@ impl Database {
                     .execute(&mut conn)
                 {
                     Ok(_) => Ok(()),
-                    Err(error) => Err(DatabaseError::new(
-                        format!("Error: {}", error.to_string()).as_str(),
-                    )),
+                    Err(error) => Err(error.into()),
                 }
             }
             Err(r2d2_error) => Err(DatabaseError::new(
-                format!("r2d2 Error: {}", r2d2_error.to_string()).as_str(),
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
             )),
         }
     }
 
-    pub fn user_update(&self, user_model: User) -> Result<(), DatabaseError> {
+    // an empty password keeps the stored hash, so a profile edit doesn't pay for a new one
+    pub fn user_update(&self, mut user_model: User) -> Result<(), DatabaseError> {
+        let changes_password = !user_model.password.is_empty();
+        if changes_password {
+            user_model.password = hash_password(&user_model.password)?;
+        }
         let conn_result = self.pool.get();
         match conn_result {
             Ok(mut conn) => {
-                match diesel::update(users.find(&user_model.uuid))
-                    .set(&user_model)
-                    .execute(&mut conn)
-                {
+                let updated = match changes_password {
+                    true => diesel::update(users.find(&user_model.uuid))
+                        .set(&user_model)
+                        .execute(&mut conn),
+                    false => diesel::update(users.find(&user_model.uuid))
+                        .set((
+                            username.eq(&user_model.username),
+                            email.eq(&user_model.email),
+                            role.eq(&user_model.role),
+                            verified.eq(user_model.verified),
+                        ))
+                        .execute(&mut conn),
+                };
+                match updated {
                     Ok(_) => Ok(()),
-                    Err(error) => Err(DatabaseError::new(
-                        format!("Error: {}", error.to_string()).as_str(),
-                    )),
+                    Err(error) => Err(error.into()),
                 }
             }
             Err(r2d2_error) => Err(DatabaseError::new(
-                format!("r2d2 Error: {}", r2d2_error.to_string()).as_str(),
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
             )),
         }
     }
 
+    // checks a login, a plaintext or outdated hash that matches is replaced with a fresh hash
+    pub fn user_verify(
+        &self,
+        user_name: &str,
+        user_password: &str,
+    ) -> Result<Option<User>, DatabaseError> {
+        let mut conn = match self.pool.get() {
+            Ok(conn) => conn,
+            Err(r2d2_error) => {
+                return Err(DatabaseError::new(
+                    format!("r2d2 Error: {}", r2d2_error).as_str(),
+                ))
+            }
+        };
+
+        // names are unique ignoring case, so they log in ignoring case too
+        let user = users
+            .filter(lower(username).eq(lower(user_name)))
+            .select(User::as_select())
+            .first(&mut conn)
+            .optional()
+            .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str()))?;
+        let mut user = match user {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        match password::verify(user_password, &user.password) {
+            Verified::Valid => Ok(Some(user)),
+            Verified::Rehash => {
+                user.password = hash_password(user_password)?;
+                diesel::update(users.find(&user.uuid))
+                    .set(password.eq(&user.password))
+                    .execute(&mut conn)
+                    .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str()))?;
+                Ok(Some(user))
+            }
+            Verified::Invalid => Ok(None),
+        }
+    }
+
     pub fn user_delete(&self, user_uuid: &String) -> Result<(), DatabaseError> {
         let conn_result = self.pool.get();
         match conn_result {
-            Ok(mut conn) => match diesel::delete(users.find(user_uuid)).execute(&mut conn) {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| Self::remove_users(conn, &[user_uuid]))
+                .map(|_| ())
+                .map_err(|error: diesel::result::Error| {
+                    DatabaseError::new(format!("Error: {}", error).as_str())
+                }),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    // accounts that signed up and never confirmed are removed once their links expire, so nobody
+    // can hold on to a username or address they don't own. only an expired link marks an account
+    // as stale, unconfirmed accounts made any other way are left alone
+    pub fn user_register(
+        &self,
+        mut user_model: User,
+        verification: &Verification,
+        now: i64,
+    ) -> Result<(), DatabaseError> {
+        user_model.password = hash_password(&user_model.password)?;
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| {
+                    let expired = verifications::table
+                        .filter(verifications::expires.le(now))
+                        .filter(verifications::email.is_null())
+                        .select(verifications::user);
+                    let pending = verifications::table
+                        .filter(verifications::expires.gt(now))
+                        .select(verifications::user);
+                    let stale: Vec<String> = users
+                        .filter(verified.eq(false))
+                        .filter(uuid.eq_any(expired))
+                        .filter(uuid.ne_all(pending))
+                        .select(uuid)
+                        .load(conn)?;
+                    Self::remove_users(conn, &stale)?;
+
+                    diesel::insert_into(users)
+                        .values(&user_model)
+                        .execute(conn)?;
+                    diesel::insert_into(verifications::table)
+                        .values(verification)
+                        .execute(conn)
+                })
+                .map(|_| ())
+                .map_err(DatabaseError::from),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    // a link can only be used once, any others sent to the same user stop working with it. a link
+    // for a new address swaps it in, which fails as a conflict if it was taken in the meantime
+    pub fn verification_confirm(&self, token: &str, now: i64) -> Result<bool, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| {
+                    let found = verifications::table
+                        .find(token)
+                        .select(Verification::as_select())
+                        .first(conn)
+                        .optional()?;
+                    let found = match found {
+                        Some(found) if found.is_valid(now) => found,
+                        _ => return Ok(false),
+                    };
+                    match &found.email {
+                        Some(new_email) => diesel::update(users.find(&found.user))
+                            .set((email.eq(new_email), verified.eq(true)))
+                            .execute(conn)?,
+                        None => diesel::update(users.find(&found.user))
+                            .set(verified.eq(true))
+                            .execute(conn)?,
+                    };
+                    diesel::delete(
+                        verifications::table.filter(verifications::user.eq(&found.user)),
+                    )
+                    .execute(conn)?;
+                    Ok(true)
+                })
+                .map_err(|error: diesel::result::Error| DatabaseError::from(error)),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    // only the newest change is waiting, asking again replaces any earlier one but leaves the
+    // sign up link alone
+    pub fn email_change(&self, verification: &Verification) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| {
+                    diesel::delete(
+                        verifications::table
+                            .filter(verifications::user.eq(&verification.user))
+                            .filter(verifications::email.is_not_null()),
+                    )
+                    .execute(conn)?;
+                    diesel::insert_into(verifications::table)
+                        .values(verification)
+                        .execute(conn)
+                })
+                .map(|_| ())
+                .map_err(DatabaseError::from),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn user_by_email(&self, user_email: &str) -> Result<Option<User>, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => users
+                .filter(lower(email).eq(lower(user_email.trim())))
+                .select(User::as_select())
+                .first(&mut conn)
+                .optional()
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    // only the newest link works, asking again replaces any earlier ones
+    pub fn reset_add(&self, reset: &PasswordReset, now: i64) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| {
+                    diesel::delete(
+                        password_resets::table.filter(
+                            password_resets::expires
+                                .le(now)
+                                .or(password_resets::user.eq(&reset.user)),
+                        ),
+                    )
+                    .execute(conn)?;
+                    diesel::insert_into(password_resets::table)
+                        .values(reset)
+                        .execute(conn)
+                })
+                .map(|_| ())
+                .map_err(|error: diesel::result::Error| {
+                    DatabaseError::new(format!("Error: {}", error).as_str())
+                }),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    // sets the new password and ends every login of the user, the link came through their mail
+    // so it confirms the address as well
+    pub fn reset_confirm(
+        &self,
+        token: &str,
+        new_password: &str,
+        now: i64,
+    ) -> Result<bool, DatabaseError> {
+        let new_password = hash_password(new_password)?;
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| {
+                    let found = password_resets::table
+                        .find(token)
+                        .select(PasswordReset::as_select())
+                        .first(conn)
+                        .optional()?;
+                    let found = match found {
+                        Some(found) if found.is_valid(now) => found,
+                        _ => return Ok(false),
+                    };
+                    diesel::update(users.find(&found.user))
+                        .set((password.eq(&new_password), verified.eq(true)))
+                        .execute(conn)?;
+                    diesel::delete(
+                        password_resets::table.filter(password_resets::user.eq(&found.user)),
+                    )
+                    .execute(conn)?;
+                    diesel::delete(logins::table.filter(logins::user.eq(&found.user)))
+                        .execute(conn)?;
+                    Ok(true)
+                })
+                .map_err(|error: diesel::result::Error| {
+                    DatabaseError::new(format!("Error: {}", error).as_str())
+                }),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    fn remove_users<S: AsRef<str>>(conn: &mut SqliteConnection, uuids: &[S]) -> QueryResult<usize> {
+        let uuids: Vec<&str> = uuids.iter().map(AsRef::as_ref).collect();
+        diesel::delete(logins::table.filter(logins::user.eq_any(&uuids))).execute(conn)?;
+        diesel::delete(verifications::table.filter(verifications::user.eq_any(&uuids)))
+            .execute(conn)?;
+        diesel::delete(password_resets::table.filter(password_resets::user.eq_any(&uuids)))
+            .execute(conn)?;
+        diesel::delete(quiz_shares::table.filter(quiz_shares::user.eq_any(&uuids)))
+            .execute(conn)?;
+        diesel::update(quizzes::table.filter(quizzes::owner.eq_any(&uuids)))
+            .set(quizzes::owner.eq(None::<String>))
+            .execute(conn)?;
+        diesel::delete(users.filter(uuid.eq_any(&uuids))).execute(conn)
+    }
+
+    // expired logins are cleared out whenever someone logs in, nothing else needs them
+    pub fn login_add(&self, login: &Login) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| {
+                    diesel::delete(logins::table.filter(logins::expires.le(login.created)))
+                        .execute(conn)?;
+                    diesel::insert_into(logins::table)
+                        .values(login)
+                        .execute(conn)
+                })
+                .map(|_| ())
+                .map_err(|error: diesel::result::Error| {
+                    DatabaseError::new(format!("Error: {}", error).as_str())
+                }),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn login_user(&self, token: &str, now: i64) -> Result<Option<User>, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => logins::table
+                .find(token)
+                .inner_join(users)
+                .select((Login::as_select(), User::as_select()))
+                .first::<(Login, User)>(&mut conn)
+                .optional()
+                .map(|found| {
+                    found
+                        .filter(|(login, _)| login.is_valid(now))
+                        .map(|(_, user)| user)
+                })
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn login_delete(&self, token: &str) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => match diesel::delete(logins::table.find(token)).execute(&mut conn) {
                 Ok(_) => Ok(()),
-                Err(error) => Err(DatabaseError::new(
-                    format!("Error: {}", error.to_string()).as_str(),
-                )),
+                Err(error) => Err(DatabaseError::new(format!("Error: {}", error).as_str())),
             },
             Err(r2d2_error) => Err(DatabaseError::new(
-                format!("r2d2 Error: {}", r2d2_error.to_string()).as_str(),
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn quiz_exists(&self, quiz_uuid: &String) -> Result<bool, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => quizzes::table
+                .find(quiz_uuid)
+                .select(quizzes::uuid)
+                .first::<String>(&mut conn)
+                .optional()
+                .map(|quiz| quiz.is_some())
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    // with a user only the quizzes they own or have been shared with them are listed
+    pub fn quiz_list(
+        &self,
+        limit: u64,
+        user_uuid: Option<&str>,
+    ) -> Result<Vec<Quiz>, DatabaseError> {
+        let mut limit: i64 = limit as i64;
+        if limit == 0 {
+            limit = i64::MAX;
+        }
+
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .transaction(|conn| {
+                    let mut query = quizzes::table.into_boxed();
+                    if let Some(user_uuid) = user_uuid {
+                        let shared = quiz_shares::table
+                            .filter(quiz_shares::user.eq(user_uuid))
+                            .select(quiz_shares::quiz);
+                        query = query.filter(
+                            quizzes::owner
+                                .eq(user_uuid)
+                                .or(quizzes::uuid.eq_any(shared)),
+                        );
+                    }
+                    let rows = query
+                        .order(quizzes::name)
+                        .limit(limit)
+                        .select(QuizRow::as_select())
+                        .load(conn)?;
+                    Self::load_quizzes(conn, rows)
+                })
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn quiz_get(&self, quiz_uuid: &String) -> Result<Option<Quiz>, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .transaction(|conn| {
+                    let rows = quizzes::table
+                        .find(quiz_uuid)
+                        .select(QuizRow::as_select())
+                        .load(conn)?;
+                    Self::load_quizzes(conn, rows)
+                })
+                .map(|quizzes| quizzes.into_iter().next())
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn quiz_access(&self, quiz_uuid: &String) -> Result<Option<QuizAccess>, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .transaction(|conn| {
+                    let owner = quizzes::table
+                        .find(quiz_uuid)
+                        .select(quizzes::owner)
+                        .first::<Option<String>>(conn)
+                        .optional()?;
+                    let owner = match owner {
+                        Some(owner) => owner,
+                        None => return Ok(None),
+                    };
+                    let shared = quiz_shares::table
+                        .filter(quiz_shares::quiz.eq(quiz_uuid))
+                        .select(quiz_shares::user)
+                        .load(conn)?;
+                    Ok(Some(QuizAccess { owner, shared }))
+                })
+                .map_err(|error: diesel::result::Error| {
+                    DatabaseError::new(format!("Error: {}", error).as_str())
+                }),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn quiz_share(&self, quiz_uuid: &String, user_uuid: &String) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => match diesel::insert_or_ignore_into(quiz_shares::table)
+                .values((
+                    quiz_shares::quiz.eq(quiz_uuid),
+                    quiz_shares::user.eq(user_uuid),
+                ))
+                .execute(&mut conn)
+            {
+                Ok(_) => Ok(()),
+                Err(error) => Err(DatabaseError::new(format!("Error: {}", error).as_str())),
+            },
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn quiz_unshare(
+        &self,
+        quiz_uuid: &String,
+        user_uuid: &String,
+    ) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => match diesel::delete(quiz_shares::table.find((quiz_uuid, user_uuid)))
+                .execute(&mut conn)
+            {
+                Ok(_) => Ok(()),
+                Err(error) => Err(DatabaseError::new(format!("Error: {}", error).as_str())),
+            },
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn quiz_add(&self, quiz: &Quiz, owner: &str) -> Result<(), DatabaseError> {
+        let (mut quiz_row, question_rows, answer_rows) = quiz.rows();
+        quiz_row.owner = Some(owner.to_string());
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .transaction(|conn| {
+                    diesel::insert_into(quizzes::table)
+                        .values(&quiz_row)
+                        .execute(conn)?;
+                    Self::insert_questions(conn, &question_rows, &answer_rows)
+                })
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    // the questions are replaced wholesale, positions make diffing them more trouble than it's worth
+    pub fn quiz_update(&self, quiz: &Quiz) -> Result<(), DatabaseError> {
+        let (quiz_row, question_rows, answer_rows) = quiz.rows();
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .transaction(|conn| {
+                    let updated = diesel::update(quizzes::table.find(&quiz_row.uuid))
+                        .set(&quiz_row)
+                        .execute(conn)?;
+                    if updated == 0 {
+                        return Err(diesel::result::Error::NotFound);
+                    }
+                    Self::delete_questions(conn, &quiz_row.uuid)?;
+                    Self::insert_questions(conn, &question_rows, &answer_rows)
+                })
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn quiz_delete(&self, quiz_uuid: &String) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .transaction(|conn| {
+                    Self::delete_questions(conn, quiz_uuid)?;
+                    diesel::delete(quiz_shares::table.filter(quiz_shares::quiz.eq(quiz_uuid)))
+                        .execute(conn)?;
+                    diesel::delete(quizzes::table.find(quiz_uuid)).execute(conn)?;
+                    Ok(())
+                })
+                .map_err(|error: diesel::result::Error| {
+                    DatabaseError::new(format!("Error: {}", error).as_str())
+                }),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    fn load_quizzes(conn: &mut SqliteConnection, rows: Vec<QuizRow>) -> QueryResult<Vec<Quiz>> {
+        let uuids: Vec<&String> = rows.iter().map(|row| &row.uuid).collect();
+        let question_rows = questions::table
+            .filter(questions::quiz.eq_any(&uuids))
+            .order((questions::quiz, questions::position))
+            .select(QuestionRow::as_select())
+            .load(conn)?;
+        let answer_rows = answers::table
+            .filter(answers::quiz.eq_any(&uuids))
+            .order((answers::quiz, answers::question, answers::position))
+            .select(AnswerRow::as_select())
+            .load(conn)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Quiz::from_rows(row, &question_rows, &answer_rows))
+            .collect())
+    }
+
+    fn insert_questions(
+        conn: &mut SqliteConnection,
+        question_rows: &[QuestionRow],
+        answer_rows: &[AnswerRow],
+    ) -> QueryResult<()> {
+        diesel::insert_into(questions::table)
+            .values(question_rows)
+            .execute(conn)?;
+        diesel::insert_into(answers::table)
+            .values(answer_rows)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn delete_questions(conn: &mut SqliteConnection, quiz_uuid: &String) -> QueryResult<()> {
+        diesel::delete(answers::table.filter(answers::quiz.eq(quiz_uuid))).execute(conn)?;
+        diesel::delete(questions::table.filter(questions::quiz.eq(quiz_uuid))).execute(conn)?;
+        Ok(())
+    }
+
+    pub fn challenge_add(&self, challenge: Challenge) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => {
+                match diesel::insert_into(challenges::table)
+                    .values(challenge)
+                    .execute(&mut conn)
+                {
+                    Ok(_) => Ok(()),
+                    Err(error) => Err(DatabaseError::new(format!("Error: {}", error).as_str())),
+                }
+            }
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn challenge_get(
+        &self,
+        challenge_uuid: &String,
+    ) -> Result<Option<Challenge>, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => challenges::table
+                .find(challenge_uuid)
+                .select(Challenge::as_select())
+                .first(&mut conn)
+                .optional()
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn challenge_delete(&self, challenge_uuid: &String) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => conn
+                .immediate_transaction(|conn| {
+                    diesel::delete(attempts::table.filter(attempts::challenge.eq(challenge_uuid)))
+                        .execute(conn)?;
+                    diesel::delete(challenges::table.find(challenge_uuid)).execute(conn)
+                })
+                .map(|_| ())
+                .map_err(|error: diesel::result::Error| {
+                    DatabaseError::new(format!("Error: {}", error).as_str())
+                }),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn attempt_add(&self, attempt: Attempt) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => {
+                match diesel::insert_into(attempts::table)
+                    .values(attempt)
+                    .execute(&mut conn)
+                {
+                    Ok(_) => Ok(()),
+                    Err(error) => Err(DatabaseError::new(format!("Error: {}", error).as_str())),
+                }
+            }
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
             )),
         }
     }
+
+    pub fn attempt_get(&self, attempt_uuid: &String) -> Result<Option<Attempt>, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => attempts::table
+                .find(attempt_uuid)
+                .select(Attempt::as_select())
+                .first(&mut conn)
+                .optional()
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn attempt_list(&self, challenge_uuid: &String) -> Result<Vec<Attempt>, DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => attempts::table
+                .filter(attempts::challenge.eq(challenge_uuid))
+                .select(Attempt::as_select())
+                .load(&mut conn)
+                .map_err(|error| DatabaseError::new(format!("Error: {}", error).as_str())),
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+
+    pub fn attempt_update(&self, attempt: Attempt) -> Result<(), DatabaseError> {
+        let conn_result = self.pool.get();
+        match conn_result {
+            Ok(mut conn) => {
+                match diesel::update(attempts::table.find(&attempt.uuid))
+                    .set(&attempt)
+                    .execute(&mut conn)
+                {
+                    Ok(_) => Ok(()),
+                    Err(error) => Err(DatabaseError::new(format!("Error: {}", error).as_str())),
+                }
+            }
+            Err(r2d2_error) => Err(DatabaseError::new(
+                format!("r2d2 Error: {}", r2d2_error).as_str(),
+            )),
+        }
+    }
+}
+
+fn hash_password(plain: &str) -> Result<String, DatabaseError> {
+    password::hash(plain)
+        .map_err(|error| DatabaseError::new(format!("Password Error: {}", error).as_str()))
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::assertions_on_constants,
+    clippy::bool_comparison,
+    clippy::len_zero
+)]
 mod test {
-    use super::Database;
-    use crate::models::User;
+    use super::{Database, DatabaseError, DatabaseErrorKind, MIGRATIONS};
+    use crate::models::{
+        Answer, Attempt, Challenge, Login, PasswordReset, Question, Quiz, User, Verification,
+    };
+    use crate::schema;
+    use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
 
     #[test]
     fn test_conn() {
@@ -207,6 +1081,116 @@ mod test {
         drop(db);
     }
 
+    #[test]
+    fn test_run_migrations() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        db.run_migrations().unwrap();
+        assert!(db.run_migrations().unwrap().is_empty());
+
+        drop(db);
+    }
+
+    #[test]
+    fn test_unique_users_migration() {
+        use diesel::{Connection, SqliteConnection};
+        use diesel_migrations::MigrationHarness;
+
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        while conn
+            .pending_migrations(MIGRATIONS)
+            .unwrap()
+            .first()
+            .is_some_and(|migration| migration.name().version().to_string() != "20231130120000")
+        {
+            conn.run_next_migration(MIGRATIONS).unwrap();
+        }
+        diesel::sql_query(
+            "INSERT INTO users (uuid, username, password, email) VALUES \
+             ('a', 'Dave', '', 'dave@email.com'), \
+             ('b', 'dave', '', 'other@email.com'), \
+             ('c', 'Eve', '', 'DAVE@email.com')",
+        )
+        .execute(&mut conn)
+        .unwrap();
+
+        // nothing is renamed, the migration refuses to run and names the clashes
+        let error = Database::migrate(&mut conn).unwrap_err().to_string();
+        assert!(error.contains("username 'dave' is used by a, b"));
+        assert!(error.contains("email 'dave@email.com' is used by a, c"));
+        assert!(conn
+            .pending_migrations(MIGRATIONS)
+            .unwrap()
+            .iter()
+            .any(|migration| migration.name().version().to_string() == "20231130120000"));
+
+        diesel::sql_query("UPDATE users SET username = 'david' WHERE uuid = 'b'")
+            .execute(&mut conn)
+            .unwrap();
+        diesel::sql_query("UPDATE users SET email = 'eve@email.com' WHERE uuid = 'c'")
+            .execute(&mut conn)
+            .unwrap();
+        assert!(!Database::migrate(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_user_conflicts() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let user = User {
+            uuid: "567".to_string(),
+            username: "Unique".to_string(),
+            password: "a password".to_string(),
+            email: "unique@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
+        };
+        let other = User {
+            uuid: "678".to_string(),
+            username: "Other".to_string(),
+            email: "other-unique@email.com".to_string(),
+            ..user.clone()
+        };
+        for uuid in [&user.uuid, &other.uuid] {
+            db.user_delete(uuid).unwrap();
+        }
+        db.user_add(user.clone()).unwrap();
+        db.user_add(other.clone()).unwrap();
+
+        let kind = |result: Result<(), DatabaseError>| result.unwrap_err().kind();
+        assert_eq!(
+            kind(db.user_add(User {
+                username: "Fresh".to_string(),
+                email: "fresh@email.com".to_string(),
+                ..user.clone()
+            })),
+            DatabaseErrorKind::Conflict("uuid")
+        );
+        assert_eq!(
+            kind(db.user_add(User {
+                uuid: "789".to_string(),
+                username: "UNIQUE".to_string(),
+                email: "new@email.com".to_string(),
+                ..user.clone()
+            })),
+            DatabaseErrorKind::Conflict("username")
+        );
+        assert_eq!(
+            kind(db.user_update(User {
+                email: "Unique@Email.com".to_string(),
+                ..other.clone()
+            })),
+            DatabaseErrorKind::Conflict("email")
+        );
+
+        for uuid in [&user.uuid, &other.uuid] {
+            db.user_delete(uuid).unwrap();
+        }
+        drop(db);
+    }
+
     #[test]
     fn test_user_exist() {
         dotenv::dotenv().ok();
@@ -246,6 +1230,8 @@ mod test {
             username: "Dave".to_string(),
             password: "1ajwd102390boimada!!4as".to_string(),
             email: "dave@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
         };
         db.user_add(user.clone()).unwrap();
         let get_result = db.user_get(&user.uuid);
@@ -271,6 +1257,8 @@ mod test {
             username: "Bob".to_string(),
             password: "!4s789sdfgj232?!7761asd".to_string(),
             email: "bobby@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
         };
         let add_result = db.user_add(user.clone());
         match add_result {
@@ -295,6 +1283,8 @@ mod test {
             username: "Derek".to_string(),
             password: "6782%&123asdcvb!!jzxcqtry56".to_string(),
             email: "derek@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
         };
         db.user_add(user.clone()).unwrap();
         assert!(db.user_exists(&user.uuid).unwrap());
@@ -313,6 +1303,236 @@ mod test {
         drop(db);
     }
 
+    #[test]
+    fn test_user_update_password() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let mut user = User {
+            uuid: "790".to_string(),
+            username: "Dora".to_string(),
+            password: "the first password".to_string(),
+            email: "dora@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
+        };
+        db.user_delete(&user.uuid).unwrap();
+        db.user_add(user.clone()).unwrap();
+        let hash = db.user_get(&user.uuid).unwrap().password;
+
+        // no password in the edit leaves the stored hash alone
+        user.username = "Dora2".to_string();
+        user.password = String::new();
+        db.user_update(user.clone()).unwrap();
+        let stored = db.user_get(&user.uuid).unwrap();
+        assert_eq!(stored.username, "Dora2");
+        assert_eq!(stored.password, hash);
+
+        user.password = "the second password".to_string();
+        db.user_update(user.clone()).unwrap();
+        assert!(db
+            .user_verify("Dora2", "the second password")
+            .unwrap()
+            .is_some());
+        assert!(db
+            .user_verify("Dora2", "the first password")
+            .unwrap()
+            .is_none());
+
+        user.uuid = "791".to_string();
+        user.username = "Dora3".to_string();
+        user.email = "dora3@email.com".to_string();
+        user.password = String::new();
+        assert!(db.user_add(user).is_err());
+
+        db.user_delete(&"790".to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_user_verify() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let user = User {
+            uuid: "345".to_string(),
+            username: "Legacy".to_string(),
+            password: "plain-old-password".to_string(),
+            email: "legacy@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
+        };
+        db.user_add(user.clone()).unwrap();
+        assert!(db
+            .user_get(&user.uuid)
+            .unwrap()
+            .password
+            .starts_with("$argon2id$"));
+        assert!(db
+            .user_verify("Legacy", "plain-old-password")
+            .unwrap()
+            .is_some());
+        assert!(db.user_verify("Legacy", "wrong").unwrap().is_none());
+        assert!(db
+            .user_verify("LEGACY", "plain-old-password")
+            .unwrap()
+            .is_some());
+        assert!(db
+            .user_verify("Nobody", "plain-old-password")
+            .unwrap()
+            .is_none());
+
+        // rows from before hashing still hold the plaintext until the next login
+        let mut conn = db.pool.get().unwrap();
+        diesel::update(schema::users::table.find(&user.uuid))
+            .set(schema::users::password.eq(&user.password))
+            .execute(&mut conn)
+            .unwrap();
+        drop(conn);
+        let verified = db.user_verify("Legacy", "plain-old-password").unwrap();
+        assert!(verified.unwrap().password.starts_with("$argon2id$"));
+        assert!(db
+            .user_get(&user.uuid)
+            .unwrap()
+            .password
+            .starts_with("$argon2id$"));
+        assert!(db
+            .user_verify("Legacy", "plain-old-password")
+            .unwrap()
+            .is_some());
+
+        db.user_delete(&user.uuid).unwrap();
+        drop(db);
+    }
+
+    #[test]
+    fn test_user_register() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let user = |name: &str| User {
+            uuid: format!("register-{}", name),
+            username: format!("register-{}", name),
+            password: "a password".to_string(),
+            email: format!("register-{}@email.com", name),
+            role: Default::default(),
+            verified: false,
+        };
+        let (expired, unconfirmed, fresh) = (user("expired"), user("unconfirmed"), user("fresh"));
+        for user in [&expired, &unconfirmed, &fresh] {
+            db.user_delete(&user.uuid).unwrap();
+        }
+        let (_, verification) = Verification::create(&expired, 1_000);
+        db.user_register(expired.clone(), &verification, 1_000)
+            .unwrap();
+        // unconfirmed without ever having had a link, like a user an admin saved as unverified
+        db.user_add(unconfirmed.clone()).unwrap();
+
+        let now = verification.expires;
+        let (_, verification) = Verification::create(&fresh, now);
+        db.user_register(fresh.clone(), &verification, now).unwrap();
+        assert!(!db.user_exists(&expired.uuid).unwrap());
+        assert!(db.user_exists(&unconfirmed.uuid).unwrap());
+        assert!(db.user_exists(&fresh.uuid).unwrap());
+
+        for user in [&unconfirmed, &fresh] {
+            db.user_delete(&user.uuid).unwrap();
+        }
+        drop(db);
+    }
+
+    #[test]
+    fn test_email_change() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let user = User {
+            uuid: "email-change".to_string(),
+            username: "EmailChange".to_string(),
+            password: "a password".to_string(),
+            email: "old-change@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
+        };
+        db.user_delete(&user.uuid).unwrap();
+        db.user_add(user.clone()).unwrap();
+
+        let (_, first) = Verification::email_change(&user, "first-change@email.com", 1_000);
+        db.email_change(&first).unwrap();
+        let (_, second) = Verification::email_change(&user, "new-change@email.com", 1_000);
+        db.email_change(&second).unwrap();
+        assert_eq!(db.user_get(&user.uuid).unwrap().email, user.email);
+
+        assert!(!db.verification_confirm(&first.token, 2_000).unwrap());
+        assert!(db.verification_confirm(&second.token, 2_000).unwrap());
+        assert_eq!(
+            db.user_get(&user.uuid).unwrap().email,
+            "new-change@email.com"
+        );
+
+        db.user_delete(&user.uuid).unwrap();
+        drop(db);
+    }
+
+    #[test]
+    fn test_password_reset() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let user = User {
+            uuid: "456".to_string(),
+            username: "Forgetful".to_string(),
+            password: "old password".to_string(),
+            email: "forgetful@email.com".to_string(),
+            role: Default::default(),
+            verified: false,
+        };
+        db.user_delete(&user.uuid).unwrap();
+        db.user_add(user.clone()).unwrap();
+        assert_eq!(
+            db.user_by_email(" Forgetful@Email.com")
+                .unwrap()
+                .unwrap()
+                .uuid,
+            user.uuid
+        );
+        assert!(db.user_by_email("nobody@email.com").unwrap().is_none());
+
+        let (_, login) = Login::create(&user, 1_000);
+        db.login_add(&login).unwrap();
+        let (_, first) = PasswordReset::create(&user, 1_000);
+        db.reset_add(&first, 1_000).unwrap();
+        let (_, second) = PasswordReset::create(&user, 2_000);
+        db.reset_add(&second, 2_000).unwrap();
+
+        // asking again replaces the earlier link
+        assert!(!db
+            .reset_confirm(&first.token, "new password", 2_000)
+            .unwrap());
+        assert!(!db
+            .reset_confirm(&second.token, "new password", second.expires)
+            .unwrap());
+        assert!(db
+            .reset_confirm(&second.token, "new password", 3_000)
+            .unwrap());
+        assert!(!db
+            .reset_confirm(&second.token, "other password", 3_000)
+            .unwrap());
+
+        assert!(db
+            .user_verify("Forgetful", "old password")
+            .unwrap()
+            .is_none());
+        let changed = db
+            .user_verify("Forgetful", "new password")
+            .unwrap()
+            .unwrap();
+        assert!(changed.verified);
+        assert!(db.login_user(&login.token, 3_000).unwrap().is_none());
+
+        db.user_delete(&user.uuid).unwrap();
+        drop(db);
+    }
+
     #[test]
     fn test_user_delete() {
         dotenv::dotenv().ok();
@@ -323,6 +1543,8 @@ mod test {
             username: "Derek".to_string(),
             password: "6782%&123asdcvb!!jzxcqtry56".to_string(),
             email: "derek@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
         };
         db.user_add(user.clone()).unwrap();
         assert!(db.user_exists(&user.uuid).unwrap());
@@ -335,4 +1557,137 @@ mod test {
 
         drop(db);
     }
+
+    #[test]
+    fn test_challenge_attempts() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let challenge = Challenge {
+            uuid: "challenge-345".to_string(),
+            quiz: "{}".to_string(),
+            settings: "{}".to_string(),
+            deadline: 10_000,
+            created: 0,
+        };
+        db.challenge_add(challenge.clone()).unwrap();
+        assert!(db.challenge_get(&challenge.uuid).unwrap().is_some());
+
+        let mut attempt = Attempt {
+            uuid: "attempt-345".to_string(),
+            challenge: challenge.uuid.clone(),
+            name: "Dave".to_string(),
+            question: 0,
+            served: Some(0),
+            points: 0,
+            correct: 0,
+            wrong: 0,
+            streak: 0,
+            finished: None,
+        };
+        db.attempt_add(attempt.clone()).unwrap();
+        let duplicate = Attempt {
+            uuid: "attempt-346".to_string(),
+            ..attempt.clone()
+        };
+        assert!(db.attempt_add(duplicate).is_err());
+
+        attempt.points = 900;
+        attempt.served = None;
+        attempt.finished = Some(5_000);
+        db.attempt_update(attempt.clone()).unwrap();
+        let stored = db.attempt_get(&attempt.uuid).unwrap().unwrap();
+        assert_eq!(
+            (stored.points, stored.served, stored.finished),
+            (900, None, Some(5_000))
+        );
+        assert_eq!(db.attempt_list(&challenge.uuid).unwrap().len(), 1);
+
+        db.challenge_delete(&challenge.uuid).unwrap();
+        assert!(db.challenge_get(&challenge.uuid).unwrap().is_none());
+        assert!(db.attempt_get(&attempt.uuid).unwrap().is_none());
+
+        drop(db);
+    }
+
+    #[test]
+    fn test_quiz_crud() {
+        dotenv::dotenv().ok();
+
+        let db = Database::new();
+        let question = |text: &str| Question {
+            question: text.to_string(),
+            body: String::new(),
+            answers: vec![
+                Answer {
+                    option: 3,
+                    text: "Paris".to_string(),
+                    correct: true,
+                },
+                Answer {
+                    option: 1,
+                    text: "Lyon".to_string(),
+                    correct: false,
+                },
+            ],
+            time_limit: 30,
+            points: 2000,
+            double_points: true,
+        };
+        let mut quiz = Quiz {
+            uuid: "quiz-678".to_string(),
+            name: "Capitals".to_string(),
+            description: "European capitals".to_string(),
+            questions: vec![question("France?"), question("Germany?")],
+        };
+
+        db.quiz_add(&quiz, "teacher-678").unwrap();
+        assert!(db.quiz_exists(&quiz.uuid).unwrap());
+        assert_eq!(db.quiz_get(&quiz.uuid).unwrap(), Some(quiz.clone()));
+        assert!(db.quiz_add(&quiz, "teacher-678").is_err());
+
+        quiz.name = "Capital cities".to_string();
+        quiz.questions.reverse();
+        quiz.questions.pop();
+        db.quiz_update(&quiz).unwrap();
+        assert_eq!(db.quiz_get(&quiz.uuid).unwrap(), Some(quiz.clone()));
+        assert!(db
+            .quiz_list(0, None)
+            .unwrap()
+            .iter()
+            .any(|listed| listed == &quiz));
+
+        // saving the quiz keeps its owner, sharing it lists it for the other user
+        let access = db.quiz_access(&quiz.uuid).unwrap().unwrap();
+        assert_eq!(access.owner, Some("teacher-678".to_string()));
+        assert!(db.quiz_list(0, Some("teacher-679")).unwrap().is_empty());
+        db.quiz_share(&quiz.uuid, &"teacher-679".to_string())
+            .unwrap();
+        db.quiz_share(&quiz.uuid, &"teacher-679".to_string())
+            .unwrap();
+        assert_eq!(
+            db.quiz_list(0, Some("teacher-679")).unwrap(),
+            vec![quiz.clone()]
+        );
+        assert_eq!(
+            db.quiz_access(&quiz.uuid).unwrap().unwrap().shared,
+            vec!["teacher-679".to_string()]
+        );
+        db.quiz_unshare(&quiz.uuid, &"teacher-679".to_string())
+            .unwrap();
+        assert!(db.quiz_list(0, Some("teacher-679")).unwrap().is_empty());
+
+        let missing = Quiz {
+            uuid: "quiz-679".to_string(),
+            ..quiz.clone()
+        };
+        assert!(db.quiz_update(&missing).is_err());
+        assert!(!db.quiz_exists(&missing.uuid).unwrap());
+
+        db.quiz_delete(&quiz.uuid).unwrap();
+        assert!(db.quiz_get(&quiz.uuid).unwrap().is_none());
+        assert!(db.quiz_access(&quiz.uuid).unwrap().is_none());
+
+        drop(db);
+    }
 }
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
+#[serde(tag = "phase")]
+pub enum Phase {
+    #[default]
+    Lobby,
+    QuestionOpen {
+        question: usize,
+    },
+    Reveal {
+        question: usize,
+    },
+    Scoreboard {
+        question: usize,
+    },
+    Podium,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
+pub enum TeamAssignment {
+    #[default]
+    Auto,
+    Chosen,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
+pub enum TeamScore {
+    #[default]
+    Sum,
+    Average,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, TS)]
+#[serde(default)]
+pub struct Settings {
+    pub streak_bonus: Vec<i32>,
+    pub leaderboard_size: usize,
+    #[ts(type = "number")]
+    pub reconnect_grace: u64,
+    pub ban_addresses: bool,
+    pub teams: Vec<String>,
+    pub team_assignment: TeamAssignment,
+    pub team_score: TeamScore,
+    pub allow_spectators: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            streak_bonus: vec![0, 100, 200, 300, 400, 500],
+            leaderboard_size: 5,
+            reconnect_grace: 60,
+            // a whole classroom usually shares one address, so bans only cover the token by default
+            ban_addresses: false,
+            teams: Vec::new(),
+            team_assignment: TeamAssignment::Auto,
+            team_score: TeamScore::Sum,
+            allow_spectators: true,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, team) in self.teams.iter().enumerate() {
+            if team.trim().is_empty() {
+                return Err("team names must not be empty".to_string());
+            }
+            if self.teams[..index].contains(team) {
+                return Err(format!("duplicate team: {}", team));
+            }
+        }
+        Ok(())
+    }
+
+    // the last step of the curve keeps applying to longer streaks
+    pub fn streak_bonus(&self, streak: i32) -> i32 {
+        if streak <= 0 {
+            return 0;
+        }
+        let step = (streak as usize).min(self.streak_bonus.len());
+        step.checked_sub(1)
+            .and_then(|step| self.streak_bonus.get(step))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Start,
+    Next,
+    End,
+}
+
+impl Phase {
+    pub fn question(&self) -> Option<usize> {
+        match self {
+            Phase::QuestionOpen { question }
+            | Phase::Reveal { question }
+            | Phase::Scoreboard { question } => Some(*question),
+            Phase::Lobby | Phase::Podium => None,
+        }
+    }
+
+    pub fn apply(&self, command: Command, questions: usize) -> Result<Phase, String> {
+        match (*self, command) {
+            (Phase::Lobby, Command::Start) => {
+                if questions == 0 {
+                    Err("game has no questions".to_string())
+                } else {
+                    Ok(Phase::QuestionOpen { question: 0 })
+                }
+            }
+            (Phase::QuestionOpen { question }, Command::Next) => Ok(Phase::Reveal { question }),
+            (Phase::Reveal { question }, Command::Next) => Ok(Phase::Scoreboard { question }),
+            (Phase::Scoreboard { question }, Command::Next) => {
+                if question + 1 < questions {
+                    Ok(Phase::QuestionOpen {
+                        question: question + 1,
+                    })
+                } else {
+                    Ok(Phase::Podium)
+                }
+            }
+            (Phase::Podium, _) => Err("game has already finished".to_string()),
+            (_, Command::End) => Ok(Phase::Podium),
+            (Phase::Lobby, Command::Next) => Err("game has not started".to_string()),
+            (_, Command::Start) => Err("game has already started".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_game() {
+        let mut phase = Phase::Lobby;
+        phase = phase.apply(Command::Start, 2).unwrap();
+        assert_eq!(phase, Phase::QuestionOpen { question: 0 });
+        phase = phase.apply(Command::Next, 2).unwrap();
+        assert_eq!(phase, Phase::Reveal { question: 0 });
+        phase = phase.apply(Command::Next, 2).unwrap();
+        assert_eq!(phase, Phase::Scoreboard { question: 0 });
+        phase = phase.apply(Command::Next, 2).unwrap();
+        assert_eq!(phase, Phase::QuestionOpen { question: 1 });
+        phase = phase.apply(Command::Next, 2).unwrap();
+        phase = phase.apply(Command::Next, 2).unwrap();
+        phase = phase.apply(Command::Next, 2).unwrap();
+        assert_eq!(phase, Phase::Podium);
+    }
+
+    #[test]
+    fn test_illegal_transitions() {
+        assert!(Phase::Lobby.apply(Command::Start, 0).is_err());
+        assert!(Phase::Lobby.apply(Command::Next, 1).is_err());
+        assert!(Phase::Reveal { question: 0 }
+            .apply(Command::Start, 1)
+            .is_err());
+        assert!(Phase::Podium.apply(Command::End, 1).is_err());
+        assert!(Phase::Podium.apply(Command::Next, 1).is_err());
+    }
+
+    #[test]
+    fn test_streak_bonus_curve() {
+        let settings = Settings::default();
+        assert_eq!(settings.streak_bonus(0), 0);
+        assert_eq!(settings.streak_bonus(1), 0);
+        assert_eq!(settings.streak_bonus(2), 100);
+        assert_eq!(settings.streak_bonus(6), 500);
+        assert_eq!(settings.streak_bonus(40), 500);
+
+        let settings = Settings {
+            streak_bonus: Vec::new(),
+            ..Settings::default()
+        };
+        assert_eq!(settings.streak_bonus(3), 0);
+    }
+
+    #[test]
+    fn test_validate_teams() {
+        let teams = |teams: &[&str]| Settings {
+            teams: teams.iter().map(|team| team.to_string()).collect(),
+            ..Settings::default()
+        };
+        assert!(teams(&[]).validate().is_ok());
+        assert!(teams(&["Red", "Blue"]).validate().is_ok());
+        assert!(teams(&["Red", " "]).validate().is_err());
+        assert!(teams(&["Red", "Red"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_end_early() {
+        let phase = Phase::QuestionOpen { question: 0 };
+        assert_eq!(phase.apply(Command::End, 3), Ok(Phase::Podium));
+        assert_eq!(Phase::Lobby.apply(Command::End, 3), Ok(Phase::Podium));
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use ts_rs::TS;
+
+use crate::game::TeamScore;
+use crate::models::Player;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+pub struct Standing {
+    pub rank: usize,
+    pub name: String,
+    pub points: i32,
+    pub streak: i32,
+    pub delta: i32,
+}
+
+// players on equal points share a rank and the next rank is skipped, so 1, 2, 2, 4
+pub fn rank(players: &[Player], previous: &HashMap<String, usize>) -> Vec<Standing> {
+    let mut sorted: Vec<&Player> = players.iter().collect();
+    sorted.sort_by(|a, b| b.points.cmp(&a.points).then_with(|| a.name.cmp(&b.name)));
+
+    let mut standings: Vec<Standing> = Vec::with_capacity(sorted.len());
+    for (index, player) in sorted.into_iter().enumerate() {
+        let rank = match standings.last() {
+            Some(last) if last.points == player.points => last.rank,
+            _ => index + 1,
+        };
+        let delta = previous
+            .get(&player.name)
+            .map(|previous| *previous as i32 - rank as i32)
+            .unwrap_or(0);
+
+        standings.push(Standing {
+            rank,
+            name: player.name.clone(),
+            points: player.points,
+            streak: player.streak,
+            delta,
+        });
+    }
+    standings
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+pub struct TeamStanding {
+    pub rank: usize,
+    pub team: String,
+    pub points: i32,
+    pub members: usize,
+}
+
+// empty teams still show up on the board with no points
+pub fn rank_teams(players: &[Player], teams: &[String], score: TeamScore) -> Vec<TeamStanding> {
+    let mut totals: Vec<(&String, i32, usize)> = teams
+        .iter()
+        .map(|team| {
+            let members: Vec<&Player> = players
+                .iter()
+                .filter(|player| player.team.as_ref() == Some(team))
+                .collect();
+            let sum = members
+                .iter()
+                .fold(0i32, |sum, player| sum.saturating_add(player.points));
+            let points = match score {
+                TeamScore::Sum => sum,
+                TeamScore::Average if members.is_empty() => 0,
+                TeamScore::Average => (sum as f64 / members.len() as f64).round() as i32,
+            };
+            (team, points, members.len())
+        })
+        .collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut standings: Vec<TeamStanding> = Vec::with_capacity(totals.len());
+    for (index, (team, points, members)) in totals.into_iter().enumerate() {
+        let rank = match standings.last() {
+            Some(last) if last.points == points => last.rank,
+            _ => index + 1,
+        };
+        standings.push(TeamStanding {
+            rank,
+            team: team.clone(),
+            points,
+            members,
+        });
+    }
+    standings
+}
+
+pub fn ranks(standings: &[Standing]) -> HashMap<String, usize> {
+    standings
+        .iter()
+        .map(|standing| (standing.name.clone(), standing.rank))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, points: i32) -> Player {
+        let mut player = Player::new(name);
+        player.points = points;
+        player
+    }
+
+    #[test]
+    fn test_rank_with_ties() {
+        let players = vec![
+            player("Dave", 500),
+            player("Alice", 1000),
+            player("Bob", 500),
+            player("Eve", 0),
+        ];
+        let standings = rank(&players, &HashMap::new());
+        let ranked: Vec<(&str, usize)> = standings
+            .iter()
+            .map(|standing| (standing.name.as_str(), standing.rank))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("Alice", 1), ("Bob", 2), ("Dave", 2), ("Eve", 4)]
+        );
+    }
+
+    #[test]
+    fn test_rank_delta() {
+        let previous = HashMap::from([("Alice".to_string(), 1), ("Bob".to_string(), 2)]);
+        let players = vec![player("Alice", 500), player("Bob", 900)];
+        let standings = rank(&players, &previous);
+        assert_eq!(standings[0].name, "Bob");
+        assert_eq!(standings[0].delta, 1);
+        assert_eq!(standings[1].delta, -1);
+        assert_eq!(ranks(&standings)["Alice"], 2);
+    }
+
+    #[test]
+    fn test_rank_teams() {
+        let teams = vec!["Red".to_string(), "Blue".to_string(), "Green".to_string()];
+        let mut players = vec![
+            player("Alice", 1000),
+            player("Bob", 200),
+            player("Dave", 900),
+        ];
+        players[0].team = Some("Red".to_string());
+        players[1].team = Some("Red".to_string());
+        players[2].team = Some("Blue".to_string());
+
+        let standings = rank_teams(&players, &teams, TeamScore::Sum);
+        let ranked: Vec<(&str, i32, usize)> = standings
+            .iter()
+            .map(|standing| (standing.team.as_str(), standing.points, standing.rank))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("Red", 1200, 1), ("Blue", 900, 2), ("Green", 0, 3)]
+        );
+
+        let standings = rank_teams(&players, &teams, TeamScore::Average);
+        assert_eq!(standings[0].team, "Blue");
+        assert_eq!(standings[1].points, 600);
+        assert_eq!(standings[1].members, 2);
+    }
+}
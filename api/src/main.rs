@@ -1,26 +1,59 @@
+mod auth;
+mod challenge;
 mod db;
+mod game;
+mod leaderboard;
+mod mailer;
 mod models;
+mod nickname;
+mod password;
+mod permissions;
+mod pin;
+mod protocol;
+mod register;
+mod reset;
 mod schema;
+mod scoring;
 mod services;
+mod session;
 mod ws;
 
 use crate::db::Database;
+use crate::mailer::Mailer;
+use crate::nickname::NameFilter;
+use crate::ws::Server;
+use actix::Actor;
 use actix_web::{middleware, web, App, HttpServer};
 use std::env;
 
 pub struct AppState {
     pub app_name: String,
     pub database: Database,
+    pub filter: NameFilter,
+    pub mailer: Box<dyn Mailer>,
+    // where the server is reached from outside, used for links in mails
+    pub public_url: String,
+    // work like the password reset mail runs after the answer is sent, tests turn this off to
+    // wait for it before the answer instead
+    pub detach: bool,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AppState {
     pub fn new() -> Self {
-        let db = Database::new();
-        db.run_migrations();
-
         AppState {
             app_name: "app".to_string(),
-            database: db,
+            database: Database::new(),
+            filter: NameFilter::from_env(),
+            mailer: mailer::from_env().expect("failed to set up mail"),
+            public_url: env::var("PUBLIC_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:8080".to_string()),
+            detach: true,
         }
     }
 }
@@ -28,18 +61,66 @@ impl AppState {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
-    env::set_var("RUST_LOG", "actix_web=debug,actix_server=info");
+    env::set_var("RUST_LOG", "api=info,actix_web=debug,actix_server=info");
     env_logger::init();
 
-    HttpServer::new(|| {
+    // migrations run once before any worker starts, `--migrate-only` stops here for deploy scripts
+    match Database::new().run_migrations() {
+        Ok(applied) => {
+            for version in applied {
+                log::info!("applied migration {}", version);
+            }
+        }
+        Err(error) => {
+            log::error!("failed to migrate database: {}", error);
+            return Err(std::io::Error::other(error.to_string()));
+        }
+    }
+    if env::args().any(|arg| arg == "--migrate-only") {
+        return Ok(());
+    }
+    // checked before the workers start so a bad mail setup stops the server instead of a worker
+    if let Err(error) = mailer::from_env() {
+        log::error!("failed to set up mail: {}", error);
+        return Err(std::io::Error::other(error));
+    }
+
+    let server = Server::new().start();
+
+    HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(AppState::new()))
+            .app_data(web::Data::new(server.clone()))
             .wrap(middleware::Logger::default())
+            .service(services::get_ws)
             .service(services::get_health)
             .service(services::post_health)
+            .service(services::post_login)
+            .service(services::post_logout)
+            .service(services::post_register)
+            .service(services::get_verify)
+            .service(services::post_forgot)
+            .service(services::post_reset)
             .service(services::list_user)
             .service(services::get_user)
             .service(services::post_user)
+            .service(services::put_user)
+            .service(services::patch_user)
+            .service(services::delete_user)
+            .service(services::list_quiz)
+            .service(services::get_quiz)
+            .service(services::post_quiz)
+            .service(services::put_quiz)
+            .service(services::patch_quiz)
+            .service(services::delete_quiz)
+            .service(services::put_quiz_share)
+            .service(services::delete_quiz_share)
+            .service(services::post_challenge)
+            .service(services::get_challenge)
+            .service(services::post_attempt)
+            .service(services::get_attempt)
+            .service(services::post_answer)
+            .service(services::get_challenge_leaderboard)
     })
     .bind(("127.0.0.1", 8080))?
     .run()
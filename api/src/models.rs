@@ -1,7 +1,17 @@
+use crate::game::{Phase, Settings, TeamAssignment};
+use crate::leaderboard::{Standing, TeamStanding};
+use crate::permissions::Role;
+use crate::scoring::{AnswerResult, Submission};
+use actix::clock::Instant;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::Duration;
+use ts_rs::TS;
 use uuid::Uuid;
 
+#[allow(dead_code)]
 pub trait Model {}
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
@@ -10,23 +20,59 @@ pub struct Player {
     pub correct: i32,
     pub wrong: i32,
     pub points: i32,
+    pub streak: i32,
+    pub team: Option<String>,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+impl Player {
+    pub fn new(name: &str) -> Self {
+        Player {
+            name: name.to_string(),
+            correct: 0,
+            wrong: 0,
+            points: 0,
+            streak: 0,
+            team: None,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Serialize, Deserialize, TS)]
 pub struct Answer {
     pub option: i8,
     pub text: String,
     pub correct: bool,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Serialize, Deserialize, TS)]
 pub struct Question {
     pub question: String,
     pub body: String,
     pub answers: Vec<Answer>,
+    #[serde(default = "Question::default_time_limit")]
+    pub time_limit: u32,
+    #[serde(default = "Question::default_points")]
+    pub points: u32,
+    #[serde(default)]
+    pub double_points: bool,
+}
+
+impl Question {
+    pub const DEFAULT_TIME_LIMIT: u32 = 20;
+    pub const DEFAULT_POINTS: u32 = 1000;
+    pub const MAX_TIME_LIMIT: u32 = 600;
+    pub const MAX_POINTS: u32 = 10_000;
+
+    fn default_time_limit() -> u32 {
+        Self::DEFAULT_TIME_LIMIT
+    }
+
+    fn default_points() -> u32 {
+        Self::DEFAULT_POINTS
+    }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Hash, Clone, Serialize, Deserialize, TS)]
 pub struct Quiz {
     pub uuid: String,
     pub name: String,
@@ -34,17 +80,417 @@ pub struct Quiz {
     pub questions: Vec<Question>,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuizPatch {
+    pub uuid: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub questions: Option<Vec<Question>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = super::schema::quizzes)]
+pub struct QuizRow {
+    pub uuid: String,
+    pub name: String,
+    pub description: String,
+    // left out of updates when None, so saving a quiz never changes who owns it
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = super::schema::questions)]
+pub struct QuestionRow {
+    pub quiz: String,
+    pub position: i32,
+    pub question: String,
+    pub body: String,
+    pub time_limit: i32,
+    pub points: i32,
+    pub double_points: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = super::schema::answers)]
+pub struct AnswerRow {
+    pub quiz: String,
+    pub question: i32,
+    pub position: i32,
+    pub option: i32,
+    pub text: String,
+    pub correct: bool,
+}
+
+impl Quiz {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("quiz name must not be empty".to_string());
+        }
+        for (index, question) in self.questions.iter().enumerate() {
+            let number = index + 1;
+            if question.question.trim().is_empty() {
+                return Err(format!("question {} must have text", number));
+            }
+            if question.answers.len() < 2 {
+                return Err(format!("question {} needs at least two answers", number));
+            }
+            if !question.answers.iter().any(|answer| answer.correct) {
+                return Err(format!("question {} needs a correct answer", number));
+            }
+            if question.time_limit > Question::MAX_TIME_LIMIT {
+                return Err(format!(
+                    "question {} can last at most {} seconds",
+                    number,
+                    Question::MAX_TIME_LIMIT
+                ));
+            }
+            if question.points > Question::MAX_POINTS {
+                return Err(format!(
+                    "question {} can be worth at most {} points",
+                    number,
+                    Question::MAX_POINTS
+                ));
+            }
+            for (other, answer) in question.answers.iter().enumerate() {
+                if answer.text.trim().is_empty() {
+                    return Err(format!("question {} has an empty answer", number));
+                }
+                if question.answers[..other]
+                    .iter()
+                    .any(|previous| previous.option == answer.option)
+                {
+                    return Err(format!(
+                        "question {} uses option {} twice",
+                        number, answer.option
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn patch(&mut self, patch: QuizPatch) {
+        if let Some(name) = patch.name {
+            self.name = name;
+        }
+        if let Some(description) = patch.description {
+            self.description = description;
+        }
+        if let Some(questions) = patch.questions {
+            self.questions = questions;
+        }
+    }
+
+    // questions and answers are stored with their position so they load back in the same order
+    pub fn rows(&self) -> (QuizRow, Vec<QuestionRow>, Vec<AnswerRow>) {
+        let quiz = QuizRow {
+            uuid: self.uuid.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            owner: None,
+        };
+        let mut questions = Vec::with_capacity(self.questions.len());
+        let mut answers = Vec::new();
+        for (position, question) in self.questions.iter().enumerate() {
+            questions.push(QuestionRow {
+                quiz: self.uuid.clone(),
+                position: position as i32,
+                question: question.question.clone(),
+                body: question.body.clone(),
+                time_limit: question.time_limit as i32,
+                points: question.points as i32,
+                double_points: question.double_points,
+            });
+            for (index, answer) in question.answers.iter().enumerate() {
+                answers.push(AnswerRow {
+                    quiz: self.uuid.clone(),
+                    question: position as i32,
+                    position: index as i32,
+                    option: answer.option as i32,
+                    text: answer.text.clone(),
+                    correct: answer.correct,
+                });
+            }
+        }
+        (quiz, questions, answers)
+    }
+
+    // expects questions ordered by position and answers ordered by question then position
+    pub fn from_rows(quiz: QuizRow, questions: &[QuestionRow], answers: &[AnswerRow]) -> Self {
+        let questions = questions
+            .iter()
+            .filter(|question| question.quiz == quiz.uuid)
+            .map(|question| Question {
+                question: question.question.clone(),
+                body: question.body.clone(),
+                answers: answers
+                    .iter()
+                    .filter(|answer| {
+                        answer.quiz == quiz.uuid && answer.question == question.position
+                    })
+                    .map(|answer| Answer {
+                        option: answer.option as i8,
+                        text: answer.text.clone(),
+                        correct: answer.correct,
+                    })
+                    .collect(),
+                time_limit: question.time_limit as u32,
+                points: question.points as u32,
+                double_points: question.double_points,
+            })
+            .collect();
+
+        Quiz {
+            uuid: quiz.uuid,
+            name: quiz.name,
+            description: quiz.description,
+            questions,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
 pub struct Session {
     pub uuid: Uuid,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Clone)]
 pub struct Room {
     pub uuid: Uuid,
-    pub code: i8,
+    pub pin: u32,
     pub quiz: Quiz,
+    pub settings: Settings,
     pub players: Vec<Player>,
+    pub sessions: HashSet<Session>,
+    pub host: Option<Session>,
+    pub host_token: Uuid,
+    pub cohosts: HashSet<String>,
+    pub spectators: HashSet<Session>,
+    pub names: HashMap<Session, String>,
+    pub tokens: HashMap<Uuid, String>,
+    pub held: HashMap<String, Instant>,
+    pub addrs: HashMap<String, IpAddr>,
+    pub banned_tokens: HashSet<Uuid>,
+    pub banned_addrs: HashSet<IpAddr>,
+    pub phase: Phase,
+    pub answers: HashMap<String, Submission>,
+    pub results: Vec<AnswerResult>,
+    pub standings: Vec<Standing>,
+    pub team_standings: Vec<TeamStanding>,
+    pub ranks: HashMap<String, usize>,
+    pub opened: Option<Instant>,
+    pub deadline: Option<Instant>,
+    pub paused: Option<Instant>,
+    pub remaining: Option<Duration>,
+}
+
+impl Room {
+    pub fn new(uuid: Uuid, pin: u32, quiz: Quiz, settings: Settings) -> Self {
+        Room {
+            uuid,
+            pin,
+            quiz,
+            settings,
+            players: Vec::new(),
+            sessions: HashSet::new(),
+            host: None,
+            host_token: Uuid::new_v4(),
+            cohosts: HashSet::new(),
+            spectators: HashSet::new(),
+            names: HashMap::new(),
+            tokens: HashMap::new(),
+            held: HashMap::new(),
+            addrs: HashMap::new(),
+            banned_tokens: HashSet::new(),
+            banned_addrs: HashSet::new(),
+            phase: Phase::Lobby,
+            answers: HashMap::new(),
+            results: Vec::new(),
+            standings: Vec::new(),
+            team_standings: Vec::new(),
+            ranks: HashMap::new(),
+            opened: None,
+            deadline: None,
+            paused: None,
+            remaining: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn is_host(&self, session: &Session) -> bool {
+        self.host.as_ref() == Some(session)
+    }
+
+    // co-hosts share the host's control over the game but cannot appoint other co-hosts
+    pub fn can_control(&self, session: &Session) -> bool {
+        self.is_host(session)
+            || self
+                .names
+                .get(session)
+                .map(|name| self.cohosts.contains(name))
+                .unwrap_or(false)
+    }
+
+    pub fn connected_cohost(&self) -> Option<Session> {
+        self.names
+            .iter()
+            .find(|(_, name)| self.cohosts.contains(*name))
+            .map(|(session, _)| *session)
+    }
+
+    pub fn player_session(&self, name: &str) -> Option<Session> {
+        self.names
+            .iter()
+            .find(|(_, player)| *player == name)
+            .map(|(session, _)| *session)
+    }
+
+    pub fn question(&self) -> Option<&Question> {
+        self.phase
+            .question()
+            .and_then(|question| self.quiz.questions.get(question))
+    }
+
+    pub fn add_player(&mut self, session: Session, name: &str, team: Option<String>) -> Uuid {
+        let token = Uuid::new_v4();
+        let mut player = Player::new(name);
+        player.team = team;
+        self.sessions.insert(session);
+        self.names.insert(session, name.to_string());
+        self.tokens.insert(token, name.to_string());
+        self.players.push(player);
+        token
+    }
+
+    pub fn player(&self, name: &str) -> Option<&Player> {
+        self.players.iter().find(|player| player.name == name)
+    }
+
+    pub fn is_team_game(&self) -> bool {
+        !self.settings.teams.is_empty()
+    }
+
+    // picks the requested team when players may choose, otherwise the team with the fewest members
+    pub fn pick_team(&self, requested: Option<&str>) -> Result<Option<String>, String> {
+        if !self.is_team_game() {
+            return Ok(None);
+        }
+        if let (TeamAssignment::Chosen, Some(requested)) =
+            (self.settings.team_assignment, requested)
+        {
+            return match self.settings.teams.iter().find(|team| *team == requested) {
+                Some(team) => Ok(Some(team.clone())),
+                None => Err(format!("could not find team: {}", requested)),
+            };
+        }
+
+        let members = |team: &String| {
+            self.players
+                .iter()
+                .filter(|player| player.team.as_ref() == Some(team))
+                .count()
+        };
+        Ok(self
+            .settings
+            .teams
+            .iter()
+            .min_by_key(|team| members(team))
+            .cloned())
+    }
+
+    pub fn remove_player(&mut self, name: &str) {
+        self.players.retain(|player| player.name != name);
+        self.tokens.retain(|_, player| player != name);
+        self.held.remove(name);
+        self.cohosts.remove(name);
+        self.addrs.remove(name);
+    }
+
+    pub fn ban_player(&mut self, name: &str) {
+        let tokens = self
+            .tokens
+            .iter()
+            .filter(|(_, player)| *player == name)
+            .map(|(token, _)| *token);
+        self.banned_tokens.extend(tokens);
+        if self.settings.ban_addresses {
+            if let Some(addr) = self.addrs.get(name) {
+                self.banned_addrs.insert(*addr);
+            }
+        }
+    }
+
+    // answers, rankings and results are all keyed by name so they follow the player
+    pub fn rename_player(&mut self, from: &str, to: &str) {
+        fn rekey<V>(map: &mut HashMap<String, V>, from: &str, to: &str) {
+            if let Some(value) = map.remove(from) {
+                map.insert(to.to_string(), value);
+            }
+        }
+
+        for player in self.players.iter_mut().filter(|player| player.name == from) {
+            player.name = to.to_string();
+        }
+        for name in self
+            .names
+            .values_mut()
+            .chain(self.tokens.values_mut())
+            .filter(|name| *name == from)
+        {
+            *name = to.to_string();
+        }
+        if self.cohosts.remove(from) {
+            self.cohosts.insert(to.to_string());
+        }
+        for standing in self
+            .standings
+            .iter_mut()
+            .filter(|standing| standing.name == from)
+        {
+            standing.name = to.to_string();
+        }
+        for result in self.results.iter_mut().filter(|result| result.name == from) {
+            result.name = to.to_string();
+        }
+        rekey(&mut self.held, from, to);
+        rekey(&mut self.addrs, from, to);
+        rekey(&mut self.answers, from, to);
+        rekey(&mut self.ranks, from, to);
+    }
+
+    pub fn remove_session(&mut self, session: &Session) -> bool {
+        if let Some(name) = self.names.remove(session) {
+            self.remove_player(&name);
+        }
+        if self.is_host(session) {
+            self.host = None;
+        }
+        self.spectators.remove(session);
+        self.sessions.remove(session)
+    }
+
+    // keeps the player's slot so a new connection can take it over with their token
+    pub fn hold_session(&mut self, session: &Session, since: Instant) -> Option<String> {
+        let name = self.names.remove(session)?;
+        self.sessions.remove(session);
+        self.held.insert(name.clone(), since);
+        Some(name)
+    }
+
+    pub fn resume_session(&mut self, session: Session, token: &Uuid) -> Option<String> {
+        let name = self.tokens.get(token)?.clone();
+        if let Some(previous) = self.player_session(&name) {
+            self.names.remove(&previous);
+            self.sessions.remove(&previous);
+        }
+        self.held.remove(&name);
+        self.sessions.insert(session);
+        self.names.insert(session, name.clone());
+        Some(name)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable, Insertable, AsChangeset)]
@@ -52,6 +498,143 @@ pub struct Room {
 pub struct User {
     pub uuid: String,
     pub username: String,
+    // accepted from requests but never sent back, the database only ever holds the argon2 hash.
+    // left out of an edit it stays as it is
+    #[serde(default, skip_serializing)]
     pub password: String,
     pub email: String,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub verified: bool,
+}
+
+// only the hash of a login token is stored, the token itself is handed to the client once
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = super::schema::logins)]
+pub struct Login {
+    pub token: String,
+    pub user: String,
+    pub created: i64,
+    pub expires: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = super::schema::verifications)]
+pub struct Verification {
+    pub token: String,
+    pub user: String,
+    pub expires: i64,
+    // the address waiting to replace the user's current one, none for a sign up
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable, Insertable)]
+#[diesel(table_name = super::schema::password_resets)]
+pub struct PasswordReset {
+    pub token: String,
+    pub user: String,
+    pub expires: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = super::schema::challenges)]
+pub struct Challenge {
+    pub uuid: String,
+    pub quiz: String,
+    pub settings: String,
+    pub deadline: i64,
+    pub created: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = super::schema::attempts, treat_none_as_null = true)]
+pub struct Attempt {
+    pub uuid: String,
+    pub challenge: String,
+    pub name: String,
+    pub question: i32,
+    pub served: Option<i64>,
+    pub points: i32,
+    pub correct: i32,
+    pub wrong: i32,
+    pub streak: i32,
+    pub finished: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiz(answers: Vec<(i8, bool)>) -> Quiz {
+        Quiz {
+            uuid: "quiz".to_string(),
+            name: "Capitals".to_string(),
+            description: String::new(),
+            questions: vec![Question {
+                question: "Capital of France?".to_string(),
+                body: String::new(),
+                answers: answers
+                    .into_iter()
+                    .map(|(option, correct)| Answer {
+                        option,
+                        text: format!("answer {}", option),
+                        correct,
+                    })
+                    .collect(),
+                time_limit: Question::DEFAULT_TIME_LIMIT,
+                points: Question::DEFAULT_POINTS,
+                double_points: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_validate_quiz() {
+        assert!(quiz(vec![(0, true), (1, false)]).validate().is_ok());
+        assert!(quiz(vec![(0, true)]).validate().is_err());
+        assert!(quiz(vec![(0, false), (1, false)]).validate().is_err());
+        assert!(quiz(vec![(0, true), (0, false)]).validate().is_err());
+
+        let mut blank = quiz(vec![(0, true), (1, false)]);
+        blank.questions[0].question = "  ".to_string();
+        assert!(blank.validate().is_err());
+
+        let mut long = quiz(vec![(0, true), (1, false)]);
+        long.questions[0].time_limit = Question::MAX_TIME_LIMIT;
+        assert!(long.validate().is_ok());
+        long.questions[0].time_limit = Question::MAX_TIME_LIMIT + 1;
+        assert!(long.validate().is_err());
+
+        let mut rich = quiz(vec![(0, true), (1, false)]);
+        rich.questions[0].points = Question::MAX_POINTS;
+        assert!(rich.validate().is_ok());
+        rich.questions[0].points = u32::MAX;
+        assert!(rich.validate().is_err());
+    }
+
+    #[test]
+    fn test_patch_quiz() {
+        let mut patched = quiz(vec![(0, true), (1, false)]);
+        patched.patch(QuizPatch {
+            uuid: "quiz".to_string(),
+            description: Some("Europe".to_string()),
+            ..QuizPatch::default()
+        });
+        assert_eq!(patched.name, "Capitals");
+        assert_eq!(patched.description, "Europe");
+        assert_eq!(patched.questions.len(), 1);
+    }
+
+    #[test]
+    fn test_user_password_not_serialized() {
+        let user: User = serde_json::from_str(
+            r#"{"uuid": "1", "username": "Dave", "password": "secret", "email": "dave@email.com"}"#,
+        )
+        .unwrap();
+        assert_eq!(user.password, "secret");
+        let json = serde_json::to_value(&user).unwrap();
+        assert!(json.get("password").is_none());
+        assert_eq!(json["username"], "Dave");
+    }
 }
@@ -0,0 +1,199 @@
+use std::{env, fs};
+
+pub const MAX_LENGTH: usize = 20;
+
+const DEFAULT_BLOCKLIST: &[&str] = &[
+    "fuck", "shit", "cunt", "bitch", "whore", "slut", "porn", "penis", "vagina", "nazi",
+];
+
+#[derive(Debug, Clone)]
+pub struct NameFilter {
+    blocked: Vec<String>,
+}
+
+impl Default for NameFilter {
+    fn default() -> Self {
+        NameFilter::new(DEFAULT_BLOCKLIST)
+    }
+}
+
+impl NameFilter {
+    pub fn new<S: AsRef<str>>(words: &[S]) -> Self {
+        NameFilter {
+            blocked: words
+                .iter()
+                .map(|word| {
+                    word.as_ref()
+                        .chars()
+                        .flat_map(char::to_lowercase)
+                        .map(unleet)
+                        .filter(|c| c.is_alphanumeric())
+                        .collect::<String>()
+                })
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    // NICKNAME_BLOCKLIST points at a file with one word per line, it replaces the built in list
+    pub fn from_env() -> Self {
+        let path = match env::var("NICKNAME_BLOCKLIST") {
+            Ok(path) => path,
+            Err(_) => return NameFilter::default(),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let words: Vec<&str> = contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .collect();
+                NameFilter::new(&words)
+            }
+            Err(error) => {
+                log::error!("failed to read nickname block list {}: {}", path, error);
+                NameFilter::default()
+            }
+        }
+    }
+
+    // returns the nickname as it should be shown, with surrounding and repeated whitespace removed
+    pub fn check(&self, name: &str) -> Result<String, String> {
+        let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if name.is_empty() {
+            return Err("nickname must not be empty".to_string());
+        }
+        if name.chars().count() > MAX_LENGTH {
+            return Err(format!(
+                "nickname must be at most {} characters",
+                MAX_LENGTH
+            ));
+        }
+        if name.chars().any(char::is_control) {
+            return Err("nickname contains invalid characters".to_string());
+        }
+
+        let blocked = words(&name).iter().any(|word| {
+            let collapsed = collapse(word);
+            self.blocked
+                .iter()
+                .any(|entry| entry == word || *entry == collapsed)
+        });
+        if blocked {
+            return Err("nickname is not allowed".to_string());
+        }
+        Ok(name)
+    }
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' | '|' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        c => c,
+    }
+}
+
+// whole words are compared rather than substrings, so "Scunthorpe" and "Ash Italia" are fine.
+// words break on anything that isn't a letter or digit and at camel case humps, letters spelled
+// out one at a time ("s h i t") are put back together, and the x's gamer tags are wrapped in
+// ("xXnameXx") are dropped
+fn words(name: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut after_lower = false;
+    for c in name.chars() {
+        let mapped: Vec<char> = c.to_lowercase().map(unleet).collect();
+        let letter = mapped.iter().all(|c| c.is_alphanumeric());
+        if !letter || (c.is_uppercase() && after_lower) {
+            parts.push(std::mem::take(&mut current));
+        }
+        if letter {
+            current.extend(mapped);
+        }
+        after_lower = c.is_lowercase();
+    }
+    parts.push(current);
+
+    let mut words: Vec<String> = Vec::new();
+    let mut spelling = false;
+    for part in parts.into_iter().filter(|part| !part.is_empty()) {
+        let single = part.chars().count() == 1;
+        match words.last_mut() {
+            Some(word) if single && spelling => word.push_str(&part),
+            _ => words.push(part),
+        }
+        spelling = single;
+    }
+    words
+        .iter()
+        .map(|word| word.trim_matches('x').to_string())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+// stretched letters, "shiiit" is still "shit"
+fn collapse(word: &str) -> String {
+    let mut collapsed = String::with_capacity(word.len());
+    for c in word.chars() {
+        if !collapsed.ends_with(c) {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_cleans_whitespace() {
+        let filter = NameFilter::default();
+        assert_eq!(
+            filter.check("  Dave   the  Rave "),
+            Ok("Dave the Rave".to_string())
+        );
+        assert!(filter.check("   ").is_err());
+        assert!(filter.check("a\u{0007}b").is_err());
+        assert!(filter.check(&"x".repeat(MAX_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_check_blocks_disguised_words() {
+        let filter = NameFilter::default();
+        assert!(filter.check("SH1T").is_err());
+        assert!(filter.check("s h i i i t").is_err());
+        assert!(filter.check("xX$hitXx").is_err());
+        assert!(filter.check("Dickens").is_ok());
+        assert!(filter.check("DaveShitFace").is_err());
+        assert!(filter.check("fuuuck").is_err());
+    }
+
+    #[test]
+    fn test_check_allows_innocent_names() {
+        let filter = NameFilter::default();
+        assert!(filter.check("Scunthorpe").is_ok());
+        assert!(filter.check("Matsushita").is_ok());
+        assert!(filter.check("Ash Italia").is_ok());
+        assert!(filter.check("Essex").is_ok());
+    }
+
+    #[test]
+    fn test_custom_block_list() {
+        let filter = NameFilter::new(&["banana"]);
+        assert!(filter.check("B4NANA").is_err());
+        assert!(filter.check("shit").is_ok());
+
+        let filter = NameFilter::new(&["ass"]);
+        assert!(filter.check("a s s").is_err());
+        assert!(filter.check("Thomas").is_ok());
+        assert!(filter.check("Lucas").is_ok());
+        assert!(filter.check("Jason").is_ok());
+    }
+}
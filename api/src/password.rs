@@ -0,0 +1,96 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params};
+use rand::rngs::OsRng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verified {
+    Valid,
+    // the password matched but was stored in plaintext or with older parameters
+    Rehash,
+    Invalid,
+}
+
+// argon2id with the crate's default cost, hashes are stored as PHC strings so the parameters
+// travel with them and can be raised later without breaking existing rows
+pub fn hash(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|error| error.to_string())
+}
+
+pub fn is_hash(stored: &str) -> bool {
+    stored.starts_with("$argon2")
+}
+
+// rows written before passwords were hashed hold the plaintext, those still verify once so the
+// caller can store a hash in their place
+pub fn verify(password: &str, stored: &str) -> Verified {
+    if !is_hash(stored) {
+        return match constant_eq(password.as_bytes(), stored.as_bytes()) {
+            true => Verified::Rehash,
+            false => Verified::Invalid,
+        };
+    }
+
+    let parsed = match PasswordHash::new(stored) {
+        Ok(parsed) => parsed,
+        Err(_) => return Verified::Invalid,
+    };
+    if Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_err()
+    {
+        return Verified::Invalid;
+    }
+
+    let defaults = Params::default();
+    let current = parsed.algorithm == Algorithm::Argon2id.ident()
+        && Params::try_from(&parsed).is_ok_and(|params| {
+            (params.m_cost(), params.t_cost(), params.p_cost())
+                == (defaults.m_cost(), defaults.t_cost(), defaults.p_cost())
+        });
+    match current {
+        true => Verified::Valid,
+        false => Verified::Rehash,
+    }
+}
+
+fn constant_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let stored = hash("correct horse").unwrap();
+        assert!(stored.starts_with("$argon2id$"));
+        assert_ne!(stored, hash("correct horse").unwrap());
+        assert_eq!(verify("correct horse", &stored), Verified::Valid);
+        assert_eq!(verify("battery staple", &stored), Verified::Invalid);
+    }
+
+    #[test]
+    fn test_verify_legacy_plaintext() {
+        assert_eq!(verify("hunter2", "hunter2"), Verified::Rehash);
+        assert_eq!(verify("hunter3", "hunter2"), Verified::Invalid);
+        assert_eq!(verify("", "$argon2id$broken"), Verified::Invalid);
+    }
+
+    #[test]
+    fn test_verify_old_parameters() {
+        let params = Params::new(8, 1, 1, None).unwrap();
+        let weak = Argon2::new(Algorithm::Argon2i, Default::default(), params);
+        let salt = SaltString::generate(&mut OsRng);
+        let stored = weak
+            .hash_password(b"correct horse", &salt)
+            .unwrap()
+            .to_string();
+        assert_eq!(verify("correct horse", &stored), Verified::Rehash);
+        assert_eq!(verify("battery staple", &stored), Verified::Invalid);
+    }
+}
@@ -0,0 +1,165 @@
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::sqlite::{Sqlite, SqliteValue};
+use serde::{Deserialize, Serialize};
+
+use crate::models::User;
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+pub enum Role {
+    Admin,
+    Teacher,
+    #[default]
+    Player,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "Admin",
+            Role::Teacher => "Teacher",
+            Role::Player => "Player",
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for Role {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for Role {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Sqlite>>::from_sql(bytes)?.as_str() {
+            "Admin" => Ok(Role::Admin),
+            "Teacher" => Ok(Role::Teacher),
+            "Player" => Ok(Role::Player),
+            role => Err(format!("unknown role: {}", role).into()),
+        }
+    }
+}
+
+// who may use a stored quiz, quizzes from before ownership have no owner and only admins see them
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuizAccess {
+    pub owner: Option<String>,
+    pub shared: Vec<String>,
+}
+
+impl QuizAccess {
+    pub fn is_owner(&self, user: &User) -> bool {
+        self.owner.as_ref() == Some(&user.uuid)
+    }
+
+    pub fn is_shared(&self, user: &User) -> bool {
+        self.shared.contains(&user.uuid)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action<'a> {
+    ManageUsers,
+    // seeing every quiz rather than only the ones owned by or shared with the user
+    ManageQuizzes,
+    ReadUser(&'a str),
+    EditUser(&'a str),
+    CreateQuiz,
+    EditQuiz(&'a QuizAccess),
+    HostQuiz(&'a QuizAccess),
+    // hosting a quiz sent inline rather than a stored one, and setting challenges
+    HostGame,
+}
+
+// every permission rule lives here, handlers only name the action they are about to take
+pub fn allowed(user: &User, action: Action) -> bool {
+    // until the email address is confirmed an account can only look after itself
+    if !user.verified {
+        return matches!(action, Action::ReadUser(uuid) | Action::EditUser(uuid) if user.uuid == uuid);
+    }
+    match (user.role, action) {
+        (Role::Admin, _) => true,
+        (_, Action::ReadUser(uuid) | Action::EditUser(uuid)) => user.uuid == uuid,
+        (Role::Teacher, Action::CreateQuiz | Action::HostGame) => true,
+        (Role::Teacher, Action::EditQuiz(access)) => access.is_owner(user),
+        (Role::Teacher, Action::HostQuiz(access)) => {
+            access.is_owner(user) || access.is_shared(user)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(uuid: &str, role: Role) -> User {
+        User {
+            uuid: uuid.to_string(),
+            username: uuid.to_string(),
+            password: String::new(),
+            email: format!("{}@email.com", uuid),
+            role,
+            verified: true,
+        }
+    }
+
+    #[test]
+    fn test_users() {
+        let admin = user("admin", Role::Admin);
+        let teacher = user("teacher", Role::Teacher);
+        let player = user("player", Role::Player);
+
+        assert!(allowed(&admin, Action::ManageUsers));
+        assert!(allowed(&admin, Action::EditUser("player")));
+        assert!(!allowed(&teacher, Action::ManageUsers));
+        assert!(!allowed(&teacher, Action::ManageQuizzes));
+        assert!(!allowed(&teacher, Action::ReadUser("player")));
+        assert!(allowed(&player, Action::ReadUser("player")));
+        assert!(allowed(&player, Action::EditUser("player")));
+        assert!(!allowed(&player, Action::EditUser("teacher")));
+    }
+
+    #[test]
+    fn test_unverified() {
+        let mut admin = user("admin", Role::Admin);
+        admin.verified = false;
+        assert!(allowed(&admin, Action::ReadUser("admin")));
+        assert!(allowed(&admin, Action::EditUser("admin")));
+        assert!(!allowed(&admin, Action::ReadUser("player")));
+        assert!(!allowed(&admin, Action::ManageUsers));
+        assert!(!allowed(&admin, Action::HostGame));
+    }
+
+    #[test]
+    fn test_quizzes() {
+        let admin = user("admin", Role::Admin);
+        let owner = user("owner", Role::Teacher);
+        let colleague = user("colleague", Role::Teacher);
+        let stranger = user("stranger", Role::Teacher);
+        let player = user("player", Role::Player);
+        let access = QuizAccess {
+            owner: Some("owner".to_string()),
+            shared: vec!["colleague".to_string(), "player".to_string()],
+        };
+
+        assert!(allowed(&owner, Action::CreateQuiz));
+        assert!(allowed(&owner, Action::EditQuiz(&access)));
+        assert!(allowed(&owner, Action::HostQuiz(&access)));
+        assert!(!allowed(&colleague, Action::EditQuiz(&access)));
+        assert!(allowed(&colleague, Action::HostQuiz(&access)));
+        assert!(!allowed(&stranger, Action::HostQuiz(&access)));
+        assert!(allowed(&admin, Action::EditQuiz(&QuizAccess::default())));
+        assert!(!allowed(&owner, Action::EditQuiz(&QuizAccess::default())));
+
+        assert!(!allowed(&player, Action::CreateQuiz));
+        assert!(!allowed(&player, Action::HostGame));
+        assert!(!allowed(&player, Action::HostQuiz(&access)));
+    }
+}
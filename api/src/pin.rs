@@ -0,0 +1,77 @@
+use rand::Rng;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub const MIN_PIN: u32 = 100_000;
+pub const MAX_PIN: u32 = 9_999_999;
+const MAX_ATTEMPTS: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct Pins {
+    rooms: HashMap<u32, Uuid>,
+}
+
+impl Pins {
+    pub fn new() -> Self {
+        Pins {
+            rooms: HashMap::new(),
+        }
+    }
+
+    pub fn issue(&mut self, room: Uuid) -> Option<u32> {
+        let mut rng = rand::thread_rng();
+        for _ in 0..MAX_ATTEMPTS {
+            let pin = rng.gen_range(MIN_PIN..=MAX_PIN);
+            if let Entry::Vacant(entry) = self.rooms.entry(pin) {
+                entry.insert(room);
+                return Some(pin);
+            }
+        }
+
+        // random probing keeps failing once the pool is nearly full, so fall back to a scan
+        let pin = (MIN_PIN..=MAX_PIN).find(|pin| !self.rooms.contains_key(pin))?;
+        self.rooms.insert(pin, room);
+        Some(pin)
+    }
+
+    pub fn get(&self, pin: u32) -> Option<Uuid> {
+        self.rooms.get(&pin).copied()
+    }
+
+    pub fn release(&mut self, pin: u32) {
+        self.rooms.remove(&pin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_issue_in_range() {
+        let mut pins = Pins::new();
+        let mut issued = HashSet::new();
+        for _ in 0..1_000 {
+            let pin = pins.issue(Uuid::new_v4()).unwrap();
+            assert!(
+                (MIN_PIN..=MAX_PIN).contains(&pin),
+                "pin {} out of range",
+                pin
+            );
+            assert!(issued.insert(pin), "pin {} issued twice", pin);
+        }
+    }
+
+    #[test]
+    fn test_get_and_release() {
+        let mut pins = Pins::new();
+        let room = Uuid::new_v4();
+        let pin = pins.issue(room).unwrap();
+        assert_eq!(pins.get(pin), Some(room));
+
+        pins.release(pin);
+        assert_eq!(pins.get(pin), None);
+    }
+}
@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::game::{Phase, Settings};
+use crate::leaderboard::{Standing, TeamStanding};
+use crate::models::{Question, Quiz, Room};
+use crate::scoring::AnswerResult;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+pub struct RoomInfo {
+    pub room: Uuid,
+    pub pin: u32,
+    pub quiz: String,
+    pub token: Option<Uuid>,
+    pub teams: Vec<String>,
+    pub team: Option<String>,
+}
+
+impl RoomInfo {
+    pub fn new(room: &Room, token: Option<Uuid>) -> Self {
+        let team = token
+            .and_then(|token| room.tokens.get(&token))
+            .and_then(|name| room.player(name))
+            .and_then(|player| player.team.clone());
+        RoomInfo {
+            room: room.uuid,
+            pin: room.pin,
+            quiz: room.quiz.name.clone(),
+            token,
+            teams: room.settings.teams.clone(),
+            team,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+pub struct AnswerOption {
+    pub option: i8,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+pub struct QuestionInfo {
+    pub question: usize,
+    pub total: usize,
+    pub text: String,
+    pub body: String,
+    pub answers: Vec<AnswerOption>,
+    pub time_limit: u32,
+}
+
+impl QuestionInfo {
+    pub fn new(index: usize, total: usize, question: &Question) -> Self {
+        QuestionInfo {
+            question: index,
+            total,
+            text: question.question.clone(),
+            body: question.body.clone(),
+            // correct flags stay on the server until the reveal
+            answers: question
+                .answers
+                .iter()
+                .map(|answer| AnswerOption {
+                    option: answer.option,
+                    text: answer.text.clone(),
+                })
+                .collect(),
+            time_limit: question.time_limit,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+#[serde(tag = "type")]
+#[ts(export)]
+pub enum ClientEvent {
+    HostCreateGame {
+        #[serde(default)]
+        quiz: Quiz,
+        #[serde(default)]
+        settings: Settings,
+        #[serde(default)]
+        login: Option<String>,
+    },
+    HostStartGame,
+    HostNext,
+    HostEndGame,
+    PlayerJoin {
+        pin: u32,
+        name: String,
+        #[serde(default)]
+        team: Option<String>,
+    },
+    SpectatorJoin {
+        pin: u32,
+    },
+    PlayerChooseTeam {
+        team: String,
+    },
+    PlayerResume {
+        token: Uuid,
+    },
+    HostResume {
+        token: Uuid,
+    },
+    HostKick {
+        name: String,
+    },
+    HostBan {
+        name: String,
+    },
+    HostRename {
+        name: String,
+        to: String,
+    },
+    HostPromote {
+        name: String,
+    },
+    HostDemote {
+        name: String,
+    },
+    PlayerAnswer {
+        question: usize,
+        option: i8,
+    },
+    Leave,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS, actix::Message)]
+#[serde(tag = "type")]
+#[rtype(result = "()")]
+#[ts(export)]
+pub enum ServerEvent {
+    Welcome {
+        version: u32,
+        session: Uuid,
+    },
+    GameCreated(RoomInfo),
+    GameJoined(RoomInfo),
+    PlayerJoined {
+        name: String,
+        team: Option<String>,
+    },
+    TeamChanged {
+        name: String,
+        team: String,
+    },
+    PlayerLeft {
+        name: Option<String>,
+    },
+    PlayerDisconnected {
+        name: String,
+    },
+    PlayerResumed {
+        name: String,
+    },
+    PlayerRenamed {
+        from: String,
+        to: String,
+    },
+    HostChanged {
+        name: Option<String>,
+    },
+    CohostsChanged {
+        names: Vec<String>,
+    },
+    GamePaused,
+    GameResumed,
+    GameClosed {
+        reason: String,
+    },
+    Kicked {
+        reason: String,
+    },
+    PhaseChanged(Phase),
+    QuestionShown(QuestionInfo),
+    Tick {
+        question: usize,
+        remaining: u32,
+    },
+    AnswerAccepted {
+        question: usize,
+    },
+    AnswerRevealed {
+        question: usize,
+        correct: Vec<i8>,
+        results: Vec<AnswerResult>,
+    },
+    Leaderboard {
+        question: Option<usize>,
+        top: Vec<Standing>,
+        you: Option<Standing>,
+    },
+    TeamLeaderboard {
+        question: Option<usize>,
+        teams: Vec<TeamStanding>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl ServerEvent {
+    pub fn error(message: &str) -> Self {
+        ServerEvent::Error {
+            message: message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_event_tagged() {
+        let event = serde_json::from_str::<ClientEvent>(
+            r#"{"type": "PlayerAnswer", "question": 2, "option": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            event,
+            ClientEvent::PlayerAnswer {
+                question: 2,
+                option: 1
+            }
+        );
+
+        let event = serde_json::from_str::<ClientEvent>(r#"{"type": "HostStartGame"}"#).unwrap();
+        assert_eq!(event, ClientEvent::HostStartGame);
+
+        let event = serde_json::from_str::<ClientEvent>(r#"{"type": "HostCreateGame"}"#).unwrap();
+        assert_eq!(
+            event,
+            ClientEvent::HostCreateGame {
+                quiz: Quiz::default(),
+                settings: Settings::default(),
+                login: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_client_event_unknown_type() {
+        let event = serde_json::from_str::<ClientEvent>(r#"{"type": "HostExplode"}"#);
+        assert!(event.is_err());
+    }
+
+    #[test]
+    fn test_server_event_tagged() {
+        let event = ServerEvent::GameJoined(RoomInfo {
+            room: Uuid::nil(),
+            pin: 123456,
+            quiz: "Capitals".to_string(),
+            token: None,
+            teams: Vec::new(),
+            team: None,
+        });
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "GameJoined");
+        assert_eq!(json["pin"], 123456);
+        assert_eq!(json["quiz"], "Capitals");
+
+        let event = ServerEvent::PhaseChanged(Phase::Reveal { question: 3 });
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "PhaseChanged");
+        assert_eq!(json["phase"], "Reveal");
+        assert_eq!(json["question"], 3);
+        assert_eq!(serde_json::from_value::<ServerEvent>(json).unwrap(), event);
+    }
+}
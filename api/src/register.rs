@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth;
+use crate::mailer::Mail;
+use crate::models::{User, Verification};
+use crate::permissions::Role;
+
+// two days in milliseconds, unverified accounts are removed once their link has expired
+pub const VERIFY_LIFETIME: i64 = 2 * 24 * 60 * 60 * 1000;
+
+pub const MIN_PASSWORD: usize = 8;
+// argon2 doesn't care about length but there is no reason to hash megabytes for anyone
+pub const MAX_PASSWORD: usize = 128;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Registration {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+}
+
+impl Registration {
+    // returns the registration as it will be stored, trimmed and with the email lowercased
+    pub fn validate(&self) -> Result<Registration, String> {
+        let username = validate_username(&self.username)?;
+        let email = validate_email(&self.email)?;
+        validate_password(&self.password)?;
+
+        Ok(Registration {
+            username,
+            email,
+            password: self.password.clone(),
+        })
+    }
+
+    pub fn user(&self) -> User {
+        User {
+            uuid: Uuid::new_v4().to_string(),
+            username: self.username.clone(),
+            password: self.password.clone(),
+            email: self.email.clone(),
+            role: Role::Player,
+            verified: false,
+        }
+    }
+}
+
+// the user routes share these with sign up, both return the value as it should be stored
+pub fn validate_username(username: &str) -> Result<String, String> {
+    let username = username.trim();
+    let length = username.chars().count();
+    if !(3..=32).contains(&length) {
+        return Err("username must be 3 to 32 characters".to_string());
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err("username may only contain letters, digits, '_', '-' and '.'".to_string());
+    }
+    Ok(username.to_string())
+}
+
+pub fn validate_email(email: &str) -> Result<String, String> {
+    let email = email.trim().to_lowercase();
+    match is_email(&email) {
+        true => Ok(email),
+        false => Err("email address is not valid".to_string()),
+    }
+}
+
+// shared with password resets so a new password follows the same rules as the first one
+pub fn validate_password(password: &str) -> Result<(), String> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD {
+        return Err(format!(
+            "password must be at least {} characters",
+            MIN_PASSWORD
+        ));
+    }
+    if length > MAX_PASSWORD {
+        return Err(format!(
+            "password must be at most {} characters",
+            MAX_PASSWORD
+        ));
+    }
+    Ok(())
+}
+
+// deliberately loose, the verification mail is the real check
+fn is_email(email: &str) -> bool {
+    let (local, domain) = match email.split_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    email.len() <= 254
+        && !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+impl Verification {
+    // like logins only the hash is stored, the token goes out in the mail
+    pub fn create(user: &User, now: i64) -> (String, Self) {
+        let token = auth::new_token();
+        let verification = Verification {
+            token: auth::token_hash(&token),
+            user: user.uuid.clone(),
+            expires: now + VERIFY_LIFETIME,
+            email: None,
+        };
+        (token, verification)
+    }
+
+    // confirms a new address before it replaces the current one
+    pub fn email_change(user: &User, email: &str, now: i64) -> (String, Self) {
+        let (token, verification) = Verification::create(user, now);
+        let verification = Verification {
+            email: Some(email.to_string()),
+            ..verification
+        };
+        (token, verification)
+    }
+
+    pub fn is_valid(&self, now: i64) -> bool {
+        now < self.expires
+    }
+}
+
+pub fn verification_mail(user: &User, public_url: &str, token: &str) -> Mail {
+    Mail {
+        to: user.email.clone(),
+        subject: "Confirm your quizo account".to_string(),
+        body: format!(
+            "Hi {},\n\nOpen this link to confirm your email address:\n\n{}/api/v1/auth/verify?token={}\n\nThe link works for two days. If you didn't sign up you can ignore this mail.",
+            user.username,
+            public_url.trim_end_matches('/'),
+            token
+        ),
+    }
+}
+
+pub fn email_change_mail(user: &User, email: &str, public_url: &str, token: &str) -> Mail {
+    Mail {
+        to: email.to_string(),
+        subject: "Confirm your new quizo email address".to_string(),
+        body: format!(
+            "Hi {},\n\nOpen this link to use this address for your account:\n\n{}/api/v1/auth/verify?token={}\n\nUntil then your account keeps its old address. The link works for two days. If you didn't ask for this you can ignore this mail.",
+            user.username,
+            public_url.trim_end_matches('/'),
+            token
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(username: &str, email: &str, password: &str) -> Registration {
+        Registration {
+            username: username.to_string(),
+            email: email.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_registration() {
+        let valid = registration(" dave_r ", " Dave@Email.com ", "correct horse")
+            .validate()
+            .unwrap();
+        assert_eq!(valid.username, "dave_r");
+        assert_eq!(valid.email, "dave@email.com");
+        assert_eq!(valid.password, "correct horse");
+
+        assert!(registration("da", "dave@email.com", "correct horse")
+            .validate()
+            .is_err());
+        assert!(registration("dave r", "dave@email.com", "correct horse")
+            .validate()
+            .is_err());
+        assert!(registration("dave", "dave@email.com", "short")
+            .validate()
+            .is_err());
+        assert!(
+            registration("dave", "dave@email.com", &"x".repeat(MAX_PASSWORD + 1))
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_is_email() {
+        assert!(is_email("dave@email.com"));
+        assert!(is_email("dave.r+quiz@school.ac.uk"));
+        assert!(!is_email("dave"));
+        assert!(!is_email("@email.com"));
+        assert!(!is_email("dave@email"));
+        assert!(!is_email("dave@@email.com"));
+        assert!(!is_email("dave@.com"));
+        assert!(!is_email("da ve@email.com"));
+    }
+
+    #[test]
+    fn test_verification() {
+        let user = registration("dave", "dave@email.com", "correct horse").user();
+        assert!(!user.verified);
+        assert_eq!(user.role, Role::Player);
+
+        let (token, verification) = Verification::create(&user, 1_000);
+        assert_eq!(verification.token, auth::token_hash(&token));
+        assert!(verification.is_valid(1_000 + VERIFY_LIFETIME - 1));
+        assert!(!verification.is_valid(1_000 + VERIFY_LIFETIME));
+
+        let (_, change) = Verification::email_change(&user, "new@email.com", 1_000);
+        assert_eq!(verification.email, None);
+        assert_eq!(change.email.as_deref(), Some("new@email.com"));
+        assert_eq!(change.expires, verification.expires);
+
+        let mail = verification_mail(&user, "https://quizo.example/", &token);
+        assert_eq!(mail.to, "dave@email.com");
+        assert!(mail.body.contains(&format!(
+            "https://quizo.example/api/v1/auth/verify?token={}",
+            token
+        )));
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth;
+use crate::mailer::Mail;
+use crate::models::{PasswordReset, User};
+
+// an hour in milliseconds, a reset link is as good as the password so it doesn't live long
+pub const RESET_LIFETIME: i64 = 60 * 60 * 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PasswordChange {
+    pub token: String,
+    pub password: String,
+}
+
+impl PasswordReset {
+    // only the hash is stored, the token itself only ever goes out in the mail
+    pub fn create(user: &User, now: i64) -> (String, Self) {
+        let token = auth::new_token();
+        let reset = PasswordReset {
+            token: auth::token_hash(&token),
+            user: user.uuid.clone(),
+            expires: now + RESET_LIFETIME,
+        };
+        (token, reset)
+    }
+
+    pub fn is_valid(&self, now: i64) -> bool {
+        now < self.expires
+    }
+}
+
+// there is no page to link to yet, so the token goes out as a code to paste into the client
+pub fn reset_mail(user: &User, token: &str) -> Mail {
+    Mail {
+        to: user.email.clone(),
+        subject: "Reset your quizo password".to_string(),
+        body: format!(
+            "Hi {},\n\nSomeone asked to reset the password for your account. Use this code to choose a new one:\n\n{}\n\nThe code works once and for an hour, setting a new password logs you out everywhere. If you didn't ask for this you can ignore this mail.",
+            user.username, token
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_reset() {
+        let user = User {
+            uuid: "user".to_string(),
+            username: "Dave".to_string(),
+            password: String::new(),
+            email: "dave@email.com".to_string(),
+            role: Default::default(),
+            verified: true,
+        };
+
+        let (token, reset) = PasswordReset::create(&user, 1_000);
+        assert_eq!(reset.token, auth::token_hash(&token));
+        assert_eq!(reset.user, "user");
+        assert!(reset.is_valid(1_000 + RESET_LIFETIME - 1));
+        assert!(!reset.is_valid(1_000 + RESET_LIFETIME));
+
+        let mail = reset_mail(&user, &token);
+        assert_eq!(mail.to, "dave@email.com");
+        assert!(mail.body.lines().any(|line| line == token));
+    }
+}
@@ -4,5 +4,117 @@ diesel::table! {
         username -> VarChar,
         password -> VarChar,
         email -> VarChar,
+        role -> VarChar,
+        verified -> Bool,
     }
 }
+
+diesel::table! {
+    verifications (token) {
+        token -> VarChar,
+        user -> VarChar,
+        expires -> BigInt,
+        email -> Nullable<VarChar>,
+    }
+}
+
+diesel::table! {
+    password_resets (token) {
+        token -> VarChar,
+        user -> VarChar,
+        expires -> BigInt,
+    }
+}
+
+diesel::table! {
+    challenges (uuid) {
+        uuid -> VarChar,
+        quiz -> Text,
+        settings -> Text,
+        deadline -> BigInt,
+        created -> BigInt,
+    }
+}
+
+diesel::table! {
+    attempts (uuid) {
+        uuid -> VarChar,
+        challenge -> VarChar,
+        name -> VarChar,
+        question -> Integer,
+        served -> Nullable<BigInt>,
+        points -> Integer,
+        correct -> Integer,
+        wrong -> Integer,
+        streak -> Integer,
+        finished -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    quizzes (uuid) {
+        uuid -> VarChar,
+        name -> VarChar,
+        description -> Text,
+        owner -> Nullable<VarChar>,
+    }
+}
+
+diesel::table! {
+    quiz_shares (quiz, user) {
+        quiz -> VarChar,
+        user -> VarChar,
+    }
+}
+
+diesel::table! {
+    questions (quiz, position) {
+        quiz -> VarChar,
+        position -> Integer,
+        question -> Text,
+        body -> Text,
+        time_limit -> Integer,
+        points -> Integer,
+        double_points -> Bool,
+    }
+}
+
+diesel::table! {
+    answers (quiz, question, position) {
+        quiz -> VarChar,
+        question -> Integer,
+        position -> Integer,
+        option -> Integer,
+        text -> Text,
+        correct -> Bool,
+    }
+}
+
+diesel::table! {
+    logins (token) {
+        token -> VarChar,
+        user -> VarChar,
+        created -> BigInt,
+        expires -> BigInt,
+    }
+}
+
+diesel::joinable!(attempts -> challenges (challenge));
+diesel::joinable!(logins -> users (user));
+diesel::joinable!(password_resets -> users (user));
+diesel::joinable!(questions -> quizzes (quiz));
+diesel::joinable!(quiz_shares -> quizzes (quiz));
+diesel::joinable!(verifications -> users (user));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    answers,
+    attempts,
+    challenges,
+    logins,
+    password_resets,
+    questions,
+    quiz_shares,
+    quizzes,
+    users,
+    verifications,
+);
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use ts_rs::TS;
+
+use crate::game::Settings;
+use crate::models::{Player, Question};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Submission {
+    pub option: i8,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, TS)]
+pub struct AnswerResult {
+    pub name: String,
+    pub option: Option<i8>,
+    pub correct: bool,
+    pub points: i32,
+    pub bonus: i32,
+    pub streak: i32,
+    pub total: i32,
+}
+
+pub fn is_correct(question: &Question, option: i8) -> bool {
+    question
+        .answers
+        .iter()
+        .any(|answer| answer.option == option && answer.correct)
+}
+
+// a correct answer is worth the full amount at 0s and half of it right at the deadline
+pub fn points(question: &Question, elapsed: Duration) -> i32 {
+    let max = question.points as f64;
+    let limit = Duration::from_secs(question.time_limit as u64).as_secs_f64();
+    let ratio = if limit > 0.0 {
+        (elapsed.as_secs_f64() / limit).min(1.0)
+    } else {
+        0.0
+    };
+
+    let mut points = (max * (1.0 - ratio / 2.0)).round() as i32;
+    if question.double_points {
+        points = points.saturating_mul(2);
+    }
+    points
+}
+
+pub fn score_question(
+    question: &Question,
+    settings: &Settings,
+    players: &mut [Player],
+    answers: &HashMap<String, Submission>,
+) -> Vec<AnswerResult> {
+    players
+        .iter_mut()
+        .map(|player| {
+            let submission = answers.get(&player.name);
+            let correct = submission
+                .map(|submission| is_correct(question, submission.option))
+                .unwrap_or(false);
+            let points = match submission {
+                Some(submission) if correct => points(question, submission.elapsed),
+                _ => 0,
+            };
+
+            if correct {
+                player.correct += 1;
+                player.streak += 1;
+            } else {
+                player.wrong += 1;
+                player.streak = 0;
+            }
+            let bonus = if correct {
+                settings.streak_bonus(player.streak)
+            } else {
+                0
+            };
+            // a hosted quiz or streak curve can ask for anything, so clamp instead of overflowing
+            let earned = points.saturating_add(bonus);
+            player.points = player.points.saturating_add(earned);
+
+            AnswerResult {
+                name: player.name.clone(),
+                option: submission.map(|submission| submission.option),
+                correct,
+                points: earned,
+                bonus,
+                streak: player.streak,
+                total: player.points,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Answer;
+
+    fn question(double_points: bool) -> Question {
+        Question {
+            question: "2 + 2?".to_string(),
+            body: String::new(),
+            answers: vec![
+                Answer {
+                    option: 0,
+                    text: "4".to_string(),
+                    correct: true,
+                },
+                Answer {
+                    option: 1,
+                    text: "5".to_string(),
+                    correct: false,
+                },
+            ],
+            time_limit: 20,
+            points: 1000,
+            double_points,
+        }
+    }
+
+    #[test]
+    fn test_faster_scores_more() {
+        let question = question(false);
+        assert_eq!(points(&question, Duration::ZERO), 1000);
+        assert_eq!(points(&question, Duration::from_secs(10)), 750);
+        assert_eq!(points(&question, Duration::from_secs(20)), 500);
+        assert_eq!(points(&question, Duration::from_secs(60)), 500);
+    }
+
+    #[test]
+    fn test_double_points() {
+        let question = question(true);
+        assert_eq!(points(&question, Duration::ZERO), 2000);
+    }
+
+    #[test]
+    fn test_huge_points_saturate() {
+        let mut question = question(true);
+        question.points = u32::MAX;
+        assert_eq!(points(&question, Duration::ZERO), i32::MAX);
+
+        let settings = Settings {
+            streak_bonus: vec![i32::MAX],
+            ..Settings::default()
+        };
+        let mut players = vec![Player::new("Alice")];
+        let answers = HashMap::from([(
+            "Alice".to_string(),
+            Submission {
+                option: 0,
+                elapsed: Duration::ZERO,
+            },
+        )]);
+        score_question(&question, &settings, &mut players, &answers);
+        let results = score_question(&question, &settings, &mut players, &answers);
+        assert_eq!(results[0].points, i32::MAX);
+        assert_eq!(players[0].points, i32::MAX);
+    }
+
+    #[test]
+    fn test_score_question() {
+        let question = question(false);
+        let mut players = vec![Player::new("Alice"), Player::new("Bob"), Player::new("Eve")];
+        let answers = HashMap::from([
+            (
+                "Alice".to_string(),
+                Submission {
+                    option: 0,
+                    elapsed: Duration::ZERO,
+                },
+            ),
+            (
+                "Bob".to_string(),
+                Submission {
+                    option: 1,
+                    elapsed: Duration::ZERO,
+                },
+            ),
+        ]);
+
+        let results = score_question(&question, &Settings::default(), &mut players, &answers);
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            (players[0].correct, players[0].wrong, players[0].points),
+            (1, 0, 1000)
+        );
+        assert_eq!(
+            (players[1].correct, players[1].wrong, players[1].points),
+            (0, 1, 0)
+        );
+        assert_eq!(
+            (players[2].correct, players[2].wrong, players[2].points),
+            (0, 1, 0)
+        );
+        assert_eq!(results[2].option, None);
+    }
+
+    #[test]
+    fn test_streak_bonus() {
+        let question = question(false);
+        let settings = Settings::default();
+        let mut players = vec![Player::new("Alice")];
+        let answer = |option| {
+            HashMap::from([(
+                "Alice".to_string(),
+                Submission {
+                    option,
+                    elapsed: Duration::ZERO,
+                },
+            )])
+        };
+
+        let results = score_question(&question, &settings, &mut players, &answer(0));
+        assert_eq!((results[0].streak, results[0].bonus), (1, 0));
+        let results = score_question(&question, &settings, &mut players, &answer(0));
+        assert_eq!((results[0].streak, results[0].bonus), (2, 100));
+        assert_eq!(results[0].points, 1100);
+        assert_eq!(players[0].points, 2100);
+
+        let results = score_question(&question, &settings, &mut players, &answer(1));
+        assert_eq!((results[0].streak, results[0].bonus), (0, 0));
+        assert_eq!(players[0].streak, 0);
+    }
+}
@@ -1,9 +1,26 @@
-use crate::models::User;
+use crate::auth::{token_hash, Auth, Credentials, LoginInfo};
+use crate::challenge::{self, Answered, NewChallenge};
+use crate::db::{Database, DatabaseError, DatabaseErrorKind};
+use crate::leaderboard;
+use crate::mailer::Mail;
+use crate::models::{
+    Attempt, Challenge, Login, PasswordReset, Player, Quiz, QuizPatch, User, Verification,
+};
+use crate::permissions::{Action, QuizAccess};
+use crate::register::{self, Registration};
+use crate::reset::{self, PasswordChange, ResetRequest};
+use crate::session::WsSession;
+use crate::ws::Server;
 use crate::AppState;
-use actix_web::{delete, error, get, http::Error, patch, post, put, web, HttpResponse, Responder};
+use actix::Addr;
+use actix_web::{
+    delete, error, get, http::Error, patch, post, put, web, HttpRequest, HttpResponse, Responder,
+};
+use actix_web_actors::ws;
 use futures::StreamExt;
-use serde::{Deserialize, Serialize};
-use std::fmt::format;
+use serde::Deserialize;
+use std::collections::HashMap;
+use uuid::Uuid;
 
 const MAX_SIZE: usize = 262_144;
 
@@ -17,6 +34,44 @@ struct UserInfo {
     uuid: String,
 }
 
+#[derive(Deserialize)]
+struct TokenInfo {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct QuizInfo {
+    uuid: String,
+}
+
+#[derive(Deserialize)]
+struct SharePath {
+    uuid: String,
+    user: String,
+}
+
+#[derive(Deserialize)]
+struct ChallengePath {
+    uuid: String,
+}
+
+#[derive(Deserialize)]
+struct AttemptPath {
+    uuid: String,
+    attempt: String,
+}
+
+#[derive(Deserialize)]
+struct NewAttempt {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct AnswerInfo {
+    question: usize,
+    option: i8,
+}
+
 #[get("/api/v1/health")]
 pub async fn get_health(data: web::Data<AppState>) -> impl Responder {
     let app_name = &data.app_name;
@@ -35,10 +90,214 @@ pub async fn post_health(data: web::Data<AppState>, info: web::Path<HealthInfo>)
     HttpResponse::Ok().body(format!("Passed Post Health Check, {}, {}", app_name, post))
 }
 
+#[get("/api/v1/ws")]
+pub async fn get_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    server: web::Data<Addr<Server>>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ip = req.peer_addr().map(|addr| addr.ip());
+    let session = WsSession::new(server.get_ref().clone(), ip, data.database.clone());
+    ws::start(session, &req, stream)
+}
+
+// argon2 is slow on purpose, so anything that hashes or checks a password runs on the blocking
+// pool instead of stalling every other connection on the worker
+async fn hashing<T, F>(data: &web::Data<AppState>, work: F) -> Result<T, DatabaseError>
+where
+    T: Send + 'static,
+    F: FnOnce(&Database) -> Result<T, DatabaseError> + Send + 'static,
+{
+    let db = data.database.clone();
+    web::block(move || work(&db))
+        .await
+        .map_err(|error| DatabaseError::new(error.to_string().as_str()))?
+}
+
+#[post("/api/v1/auth/login")]
+pub async fn post_login(
+    data: web::Data<AppState>,
+    credentials: web::Json<Credentials>,
+) -> impl Responder {
+    let credentials = credentials.into_inner();
+    let verified = hashing(&data, move |db| {
+        db.user_verify(&credentials.username, &credentials.password)
+    })
+    .await;
+    let user = match verified {
+        Ok(Some(user)) => user,
+        // the same answer for an unknown user and a wrong password
+        Ok(None) => return HttpResponse::Unauthorized().body("invalid username or password"),
+        Err(_) => {
+            return HttpResponse::BadRequest()
+                .body(format!("failure in {} to log in", data.app_name))
+        }
+    };
+
+    let (token, login) = Login::create(&user, challenge::now());
+    match data.database.login_add(&login) {
+        Ok(_) => HttpResponse::Ok().json(LoginInfo {
+            token,
+            expires: login.expires,
+            user,
+        }),
+        Err(_) => {
+            HttpResponse::BadRequest().body(format!("failure in {} to log in", data.app_name))
+        }
+    }
+}
+
+#[post("/api/v1/auth/logout")]
+pub async fn post_logout(data: web::Data<AppState>, auth: Auth) -> impl Responder {
+    match data.database.login_delete(&auth.token) {
+        Ok(_) => HttpResponse::Ok().body(format!("{} Logged out", auth.user.uuid)),
+        Err(_) => {
+            HttpResponse::BadRequest().body(format!("failure in {} to log out", data.app_name))
+        }
+    }
+}
+
+// a clashing username or email is the caller's to fix, so it gets a 409 naming the field
+fn taken(error: &DatabaseError) -> Option<HttpResponse> {
+    match error.kind() {
+        DatabaseErrorKind::Conflict(field) => {
+            Some(HttpResponse::Conflict().body(format!("{} is already taken", field)))
+        }
+        DatabaseErrorKind::Other => None,
+    }
+}
+
+// smtp sends block until the server answers, so they go to the blocking pool rather than
+// holding up every other request on the worker
+async fn send_mail(data: web::Data<AppState>, mail: Mail) -> Result<(), String> {
+    web::block(move || data.mailer.send(&mail))
+        .await
+        .map_err(|error| error.to_string())?
+}
+
+#[post("/api/v1/auth/register")]
+pub async fn post_register(
+    data: web::Data<AppState>,
+    registration: web::Json<Registration>,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+
+    let registration = match registration.validate() {
+        Ok(registration) => registration,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+    let now = challenge::now();
+    let user = registration.user();
+    let (token, verification) = Verification::create(&user, now);
+    let registered = hashing(&data, {
+        let user = user.clone();
+        move |db| db.user_register(user, &verification, now)
+    })
+    .await;
+    if let Err(error) = registered {
+        return taken(&error).unwrap_or_else(|| {
+            HttpResponse::BadRequest().body(format!("failure in {} to register", app_name))
+        });
+    }
+
+    let mail = register::verification_mail(&user, &data.public_url, &token);
+    match send_mail(data.clone(), mail).await {
+        Ok(_) => HttpResponse::Ok().json(user),
+        // without the mail the account could never be confirmed, so it goes again
+        Err(error) => {
+            log::error!("failed to send verification to {}: {}", user.email, error);
+            db.user_delete(&user.uuid).ok();
+            HttpResponse::InternalServerError().body("failed to send verification mail")
+        }
+    }
+}
+
+#[get("/api/v1/auth/verify")]
+pub async fn get_verify(data: web::Data<AppState>, info: web::Query<TokenInfo>) -> impl Responder {
+    match data
+        .database
+        .verification_confirm(&token_hash(&info.token), challenge::now())
+    {
+        Ok(true) => HttpResponse::Ok().body("email address confirmed, you can log in now"),
+        Ok(false) => HttpResponse::BadRequest().body("verification link is invalid or has expired"),
+        Err(_) => {
+            HttpResponse::BadRequest().body(format!("failure in {} to verify email", data.app_name))
+        }
+    }
+}
+
+// answers straight away and the same way whether or not the address has an account, the lookup
+// and the mail happen afterwards so neither the body nor the timing gives an account away
+#[post("/api/v1/auth/forgot")]
+pub async fn post_forgot(
+    data: web::Data<AppState>,
+    request: web::Json<ResetRequest>,
+) -> impl Responder {
+    let email = request.into_inner().email;
+    let detach = data.detach;
+    let job = actix_web::rt::spawn(async move {
+        let sent = web::block(move || send_reset(&data, &email)).await;
+        if let Err(error) = sent
+            .map_err(|error| error.to_string())
+            .and_then(|sent| sent)
+        {
+            log::error!("failed to send password reset: {}", error);
+        }
+    });
+    if !detach {
+        job.await.ok();
+    }
+    HttpResponse::Ok().body("if the address belongs to an account a reset code is on its way")
+}
+
+fn send_reset(data: &AppState, email: &str) -> Result<(), String> {
+    let db = &data.database;
+    let user = match db.user_by_email(email).map_err(|error| error.to_string())? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let now = challenge::now();
+    let (token, reset) = PasswordReset::create(&user, now);
+    db.reset_add(&reset, now)
+        .map_err(|error| error.to_string())?;
+    data.mailer.send(&reset::reset_mail(&user, &token))
+}
+
+#[post("/api/v1/auth/reset")]
+pub async fn post_reset(
+    data: web::Data<AppState>,
+    change: web::Json<PasswordChange>,
+) -> impl Responder {
+    if let Err(error) = register::validate_password(&change.password) {
+        return HttpResponse::BadRequest().body(error);
+    }
+    let change = change.into_inner();
+    let confirmed = hashing(&data, move |db| {
+        db.reset_confirm(
+            &token_hash(&change.token),
+            &change.password,
+            challenge::now(),
+        )
+    })
+    .await;
+    match confirmed {
+        Ok(true) => HttpResponse::Ok().body("password changed, log in with the new one"),
+        Ok(false) => HttpResponse::BadRequest().body("reset code is invalid or has expired"),
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failure in {} to reset password", data.app_name)),
+    }
+}
+
 #[get("/api/v1/user")]
-pub async fn list_user(data: web::Data<AppState>) -> impl Responder {
+pub async fn list_user(data: web::Data<AppState>, auth: Auth) -> impl Responder {
     let db = &data.database;
     let app_name = &data.app_name;
+    if let Err(response) = auth.require(Action::ManageUsers) {
+        return response;
+    }
 
     let user_result = db.user_list(0);
     match user_result {
@@ -48,10 +307,17 @@ pub async fn list_user(data: web::Data<AppState>) -> impl Responder {
 }
 
 #[get("/api/v1/user/{uuid}")]
-pub async fn get_user(data: web::Data<AppState>, info: web::Path<UserInfo>) -> impl Responder {
+pub async fn get_user(
+    data: web::Data<AppState>,
+    info: web::Path<UserInfo>,
+    auth: Auth,
+) -> impl Responder {
     let app_name = &data.app_name;
     let db = &data.database;
     let uuid = &info.uuid;
+    if let Err(response) = auth.require(Action::ReadUser(uuid)) {
+        return response;
+    }
 
     let user_exists_result = db.user_exists(uuid);
     match user_exists_result {
@@ -75,9 +341,15 @@ pub async fn get_user(data: web::Data<AppState>, info: web::Path<UserInfo>) -> i
 }
 
 #[post("/api/v1/user")]
-pub async fn post_user(data: web::Data<AppState>, mut payload: web::Payload) -> impl Responder {
+pub async fn post_user(
+    data: web::Data<AppState>,
+    mut payload: web::Payload,
+    auth: Auth,
+) -> impl Responder {
     let app_name = &data.app_name;
-    let db = &data.database;
+    if let Err(response) = auth.require(Action::ManageUsers) {
+        return Ok(response);
+    }
 
     let mut body = web::BytesMut::new();
     while let Some(chunk) = payload.next().await {
@@ -90,17 +362,96 @@ pub async fn post_user(data: web::Data<AppState>, mut payload: web::Payload) ->
     }
 
     // body is loaded, now we can deserialize serde-json
-    let user = serde_json::from_slice::<User>(&body)?;
-    let add_result = db.user_add(user.clone());
+    let mut user = serde_json::from_slice::<User>(&body)?;
+    if let Err(response) = validate_user(&mut user) {
+        return Ok(response);
+    }
+    // accounts made by an admin don't need confirming, everyone else goes through register
+    user.verified = true;
+    let add_result = hashing(&data, {
+        let user = user.clone();
+        move |db| db.user_add(user)
+    })
+    .await;
     match add_result {
         Ok(_) => Ok(HttpResponse::Ok().body(format!("{} Added", user.uuid))),
-        Err(_) => Ok(HttpResponse::BadRequest()
-            .body(format!("Failed to add {} in app {}", user.uuid, app_name))),
+        Err(error) => Ok(taken(&error).unwrap_or_else(|| {
+            HttpResponse::BadRequest()
+                .body(format!("Failed to add {} in app {}", user.uuid, app_name))
+        })),
+    }
+}
+
+// `verified` defaults to false when it's left out of the body, so unless an admin sends it the
+// stored value stays, and an account an admin creates counts as confirmed like with POST
+fn keep_verified(db: &Database, body: &[u8], user: &mut User) -> Result<(), DatabaseError> {
+    let sent = serde_json::from_slice::<serde_json::Value>(body)
+        .is_ok_and(|value| value.get("verified").is_some());
+    if sent {
+        return Ok(());
+    }
+    user.verified = match db.user_exists(&user.uuid)? {
+        true => db.user_get(&user.uuid)?.verified,
+        false => true,
+    };
+    Ok(())
+}
+
+const EMAIL_PENDING: &str = "the new email address is used once the link mailed to it is opened";
+
+// the same rules as sign up, the fields are stored the way `register` would store them
+fn validate_user(user: &mut User) -> Result<(), HttpResponse> {
+    user.username = register::validate_username(&user.username)
+        .map_err(|error| HttpResponse::BadRequest().body(error))?;
+    user.email = register::validate_email(&user.email)
+        .map_err(|error| HttpResponse::BadRequest().body(error))?;
+    Ok(())
+}
+
+// only an admin changes an address outright, anyone else keeps the old one until a link sent to
+// the new one is opened. returns the new address when there is one to confirm
+fn hold_email(db: &Database, auth: &Auth, user: &mut User) -> Result<Option<String>, HttpResponse> {
+    if auth.can(Action::ManageUsers) {
+        return Ok(None);
+    }
+    if user.email == auth.user.email.to_lowercase() {
+        user.email = auth.user.email.clone();
+        return Ok(None);
+    }
+    match db.user_by_email(&user.email) {
+        Ok(Some(other)) if other.uuid != user.uuid => {
+            Err(HttpResponse::Conflict().body("email is already taken"))
+        }
+        Ok(_) => Ok(Some(std::mem::replace(
+            &mut user.email,
+            auth.user.email.clone(),
+        ))),
+        Err(_) => Err(HttpResponse::BadRequest().body("failed to check email")),
+    }
+}
+
+async fn confirm_email(
+    data: &web::Data<AppState>,
+    user: &User,
+    email: &str,
+) -> Result<(), HttpResponse> {
+    let (token, verification) = Verification::email_change(user, email, challenge::now());
+    if data.database.email_change(&verification).is_err() {
+        return Err(HttpResponse::BadRequest().body("failed to start the email change"));
     }
+    let mail = register::email_change_mail(user, email, &data.public_url, &token);
+    send_mail(data.clone(), mail).await.map_err(|error| {
+        log::error!("failed to send verification to {}: {}", email, error);
+        HttpResponse::InternalServerError().body("failed to send verification mail")
+    })
 }
 
 #[put("/api/v1/user")]
-pub async fn put_user(data: web::Data<AppState>, mut payload: web::Payload) -> impl Responder {
+pub async fn put_user(
+    data: web::Data<AppState>,
+    mut payload: web::Payload,
+    auth: Auth,
+) -> impl Responder {
     let app_name = &data.app_name;
     let db = &data.database;
 
@@ -115,21 +466,49 @@ pub async fn put_user(data: web::Data<AppState>, mut payload: web::Payload) -> i
     }
 
     // body is loaded, now we can deserialize serde-json
-    let user = serde_json::from_slice::<User>(&body)?;
+    let mut user = serde_json::from_slice::<User>(&body)?;
+    if let Err(response) = auth.require(Action::EditUser(&user.uuid)) {
+        return Ok(Ok(response));
+    }
+    if !auth.can(Action::ManageUsers) {
+        user.role = auth.user.role;
+        user.verified = auth.user.verified;
+    } else if let Err(error) = keep_verified(db, &body, &mut user) {
+        return Ok(Ok(HttpResponse::BadRequest().body(format!(
+            "failed to update {} in app {}: {}",
+            &user.uuid, app_name, error
+        ))));
+    }
+    if let Err(response) = validate_user(&mut user) {
+        return Ok(Ok(response));
+    }
+    let new_email = match hold_email(db, &auth, &mut user) {
+        Ok(new_email) => new_email,
+        Err(response) => return Ok(Ok(response)),
+    };
     let exists_result = db.user_exists(&user.uuid);
+    let saved = user.clone();
     // this is such a mess
     match exists_result {
         Ok(exists) => match exists {
-            true => match db.user_update(user.clone()) {
-                Ok(_) => Ok(Ok::<HttpResponse, Error>(HttpResponse::Ok().finish())),
-                Err(_) => Ok(Ok::<HttpResponse, Error>(
-                    HttpResponse::BadRequest().finish(),
+            true => match hashing(&data, move |db| db.user_update(saved)).await {
+                Ok(_) => match new_email {
+                    Some(new_email) => match confirm_email(&data, &user, &new_email).await {
+                        Ok(_) => Ok(Ok::<HttpResponse, Error>(
+                            HttpResponse::Ok().body(EMAIL_PENDING),
+                        )),
+                        Err(response) => Ok(Ok(response)),
+                    },
+                    None => Ok(Ok::<HttpResponse, Error>(HttpResponse::Ok().finish())),
+                },
+                Err(error) => Ok(Ok::<HttpResponse, Error>(
+                    taken(&error).unwrap_or_else(|| HttpResponse::BadRequest().finish()),
                 )),
             },
-            false => match db.user_add(user.clone()) {
+            false => match hashing(&data, move |db| db.user_add(saved)).await {
                 Ok(_) => Ok(Ok::<HttpResponse, Error>(HttpResponse::Ok().finish())),
-                Err(_) => Ok(Ok::<HttpResponse, Error>(
-                    HttpResponse::BadRequest().finish(),
+                Err(error) => Ok(Ok::<HttpResponse, Error>(
+                    taken(&error).unwrap_or_else(|| HttpResponse::BadRequest().finish()),
                 )),
             },
         },
@@ -141,7 +520,11 @@ pub async fn put_user(data: web::Data<AppState>, mut payload: web::Payload) -> i
 }
 
 #[patch("/api/v1/user")]
-pub async fn patch_user(data: web::Data<AppState>, mut payload: web::Payload) -> impl Responder {
+pub async fn patch_user(
+    data: web::Data<AppState>,
+    mut payload: web::Payload,
+    auth: Auth,
+) -> impl Responder {
     let app_name = &data.app_name;
     let db = &data.database;
 
@@ -156,22 +539,63 @@ pub async fn patch_user(data: web::Data<AppState>, mut payload: web::Payload) ->
     }
 
     // body is loaded, now we can deserialize serde-json
-    let user = serde_json::from_slice::<User>(&body)?;
-    let patch_result = db.user_update(user.clone());
+    let mut user = serde_json::from_slice::<User>(&body)?;
+    if let Err(response) = auth.require(Action::EditUser(&user.uuid)) {
+        return Ok(response);
+    }
+    if !auth.can(Action::ManageUsers) {
+        user.role = auth.user.role;
+        user.verified = auth.user.verified;
+    } else if let Err(error) = keep_verified(db, &body, &mut user) {
+        return Ok(HttpResponse::BadRequest().body(format!(
+            "failed to update {} in app {}: {}",
+            &user.uuid, app_name, error
+        )));
+    }
+    if let Err(response) = validate_user(&mut user) {
+        return Ok(response);
+    }
+    let new_email = match hold_email(db, &auth, &mut user) {
+        Ok(new_email) => new_email,
+        Err(response) => return Ok(response),
+    };
+    let patch_result = hashing(&data, {
+        let user = user.clone();
+        move |db| db.user_update(user)
+    })
+    .await;
     match patch_result {
-        Ok(_) => Ok(HttpResponse::Ok().body(format!("{} Updated", &user.uuid))),
-        Err(_) => Ok(HttpResponse::BadRequest().body(format!(
-            "failed to update {} in app {}",
-            &user.uuid, app_name
-        ))),
+        Ok(_) => {
+            match new_email {
+                Some(new_email) => match confirm_email(&data, &user, &new_email).await {
+                    Ok(_) => Ok(HttpResponse::Ok()
+                        .body(format!("{} Updated, {}", &user.uuid, EMAIL_PENDING))),
+                    Err(response) => Ok(response),
+                },
+                None => Ok(HttpResponse::Ok().body(format!("{} Updated", &user.uuid))),
+            }
+        }
+        Err(error) => Ok(taken(&error).unwrap_or_else(|| {
+            HttpResponse::BadRequest().body(format!(
+                "failed to update {} in app {}",
+                &user.uuid, app_name
+            ))
+        })),
     }
 }
 
 #[delete("/api/v1/user/{uuid}")]
-pub async fn delete_user(data: web::Data<AppState>, info: web::Path<UserInfo>) -> impl Responder {
+pub async fn delete_user(
+    data: web::Data<AppState>,
+    info: web::Path<UserInfo>,
+    auth: Auth,
+) -> impl Responder {
     let app_name = &data.app_name;
     let db = &data.database;
     let uuid = &info.uuid;
+    if let Err(response) = auth.require(Action::EditUser(uuid)) {
+        return response;
+    }
 
     let user_exists_result = db.user_exists(uuid);
     match user_exists_result {
@@ -194,11 +618,493 @@ pub async fn delete_user(data: web::Data<AppState>, info: web::Path<UserInfo>) -
     }
 }
 
+fn find_access(data: &AppState, uuid: &String) -> Result<QuizAccess, HttpResponse> {
+    match data.database.quiz_access(uuid) {
+        Ok(Some(access)) => Ok(access),
+        Ok(None) => Err(HttpResponse::NotFound().body(format!("could not find quiz: {}", uuid))),
+        Err(_) => Err(HttpResponse::BadRequest().body(format!(
+            "failure in {} to get quiz: {}",
+            data.app_name, uuid
+        ))),
+    }
+}
+
+#[get("/api/v1/quiz")]
+pub async fn list_quiz(data: web::Data<AppState>, auth: Auth) -> impl Responder {
+    let db = &data.database;
+    let app_name = &data.app_name;
+    if let Err(response) = auth.require(Action::HostGame) {
+        return response;
+    }
+
+    let user = match auth.can(Action::ManageQuizzes) {
+        true => None,
+        false => Some(auth.user.uuid.as_str()),
+    };
+    match db.quiz_list(0, user) {
+        Ok(quizzes) => HttpResponse::Ok().json(quizzes),
+        Err(_) => {
+            HttpResponse::BadRequest().body(format!("failure in {} to list quizzes", app_name))
+        }
+    }
+}
+
+#[get("/api/v1/quiz/{uuid}")]
+pub async fn get_quiz(
+    data: web::Data<AppState>,
+    info: web::Path<QuizInfo>,
+    auth: Auth,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+    let uuid = &info.uuid;
+
+    let access = match find_access(&data, uuid) {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+    if let Err(response) = auth.require(Action::HostQuiz(&access)) {
+        return response;
+    }
+
+    match db.quiz_get(uuid) {
+        Ok(Some(quiz)) => HttpResponse::Ok().json(quiz),
+        Ok(None) => HttpResponse::NotFound().body(format!("could not find quiz: {}", uuid)),
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failure in {} to get quiz: {}", app_name, uuid)),
+    }
+}
+
+#[post("/api/v1/quiz")]
+pub async fn post_quiz(
+    data: web::Data<AppState>,
+    quiz: web::Json<Quiz>,
+    auth: Auth,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+    if let Err(response) = auth.require(Action::CreateQuiz) {
+        return response;
+    }
+
+    let mut quiz = quiz.into_inner();
+    if quiz.uuid.is_empty() {
+        quiz.uuid = Uuid::new_v4().to_string();
+    }
+    if let Err(error) = quiz.validate() {
+        return HttpResponse::BadRequest().body(error);
+    }
+
+    match db.quiz_add(&quiz, &auth.user.uuid) {
+        Ok(_) => HttpResponse::Ok().json(quiz),
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failed to add {} in app {}", quiz.uuid, app_name)),
+    }
+}
+
+#[put("/api/v1/quiz")]
+pub async fn put_quiz(
+    data: web::Data<AppState>,
+    quiz: web::Json<Quiz>,
+    auth: Auth,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+
+    let quiz = quiz.into_inner();
+    if let Err(error) = quiz.validate() {
+        return HttpResponse::BadRequest().body(error);
+    }
+
+    let result = match db.quiz_access(&quiz.uuid) {
+        Ok(Some(access)) => match auth.require(Action::EditQuiz(&access)) {
+            Ok(_) => db.quiz_update(&quiz),
+            Err(response) => return response,
+        },
+        Ok(None) => match auth.require(Action::CreateQuiz) {
+            Ok(_) => db.quiz_add(&quiz, &auth.user.uuid),
+            Err(response) => return response,
+        },
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(_) => HttpResponse::Ok().json(quiz),
+        Err(_) => HttpResponse::BadRequest().body(format!(
+            "failed to update {} in app {}",
+            quiz.uuid, app_name
+        )),
+    }
+}
+
+#[patch("/api/v1/quiz")]
+pub async fn patch_quiz(
+    data: web::Data<AppState>,
+    patch: web::Json<QuizPatch>,
+    auth: Auth,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+
+    let patch = patch.into_inner();
+    let access = match find_access(&data, &patch.uuid) {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+    if let Err(response) = auth.require(Action::EditQuiz(&access)) {
+        return response;
+    }
+
+    let mut quiz = match db.quiz_get(&patch.uuid) {
+        Ok(Some(quiz)) => quiz,
+        Ok(None) => {
+            return HttpResponse::NotFound().body(format!("could not find quiz: {}", patch.uuid))
+        }
+        Err(_) => {
+            return HttpResponse::BadRequest().body(format!(
+                "failure in {} to get quiz: {}",
+                app_name, patch.uuid
+            ))
+        }
+    };
+    quiz.patch(patch);
+    if let Err(error) = quiz.validate() {
+        return HttpResponse::BadRequest().body(error);
+    }
+
+    match db.quiz_update(&quiz) {
+        Ok(_) => HttpResponse::Ok().json(quiz),
+        Err(_) => HttpResponse::BadRequest().body(format!(
+            "failed to update {} in app {}",
+            quiz.uuid, app_name
+        )),
+    }
+}
+
+#[delete("/api/v1/quiz/{uuid}")]
+pub async fn delete_quiz(
+    data: web::Data<AppState>,
+    info: web::Path<QuizInfo>,
+    auth: Auth,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+    let uuid = &info.uuid;
+
+    let access = match find_access(&data, uuid) {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+    if let Err(response) = auth.require(Action::EditQuiz(&access)) {
+        return response;
+    }
+
+    match db.quiz_delete(uuid) {
+        Ok(_) => HttpResponse::Ok().body(format!("{} Deleted", uuid)),
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failed to delete {} in app {}", uuid, app_name)),
+    }
+}
+
+#[put("/api/v1/quiz/{uuid}/share/{user}")]
+pub async fn put_quiz_share(
+    data: web::Data<AppState>,
+    info: web::Path<SharePath>,
+    auth: Auth,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+
+    let access = match find_access(&data, &info.uuid) {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+    if let Err(response) = auth.require(Action::EditQuiz(&access)) {
+        return response;
+    }
+    match db.user_exists(&info.user) {
+        Ok(true) => (),
+        Ok(false) => {
+            return HttpResponse::NotFound().body(format!("could not find user: {}", info.user))
+        }
+        Err(_) => {
+            return HttpResponse::BadRequest().body(format!(
+                "failure in {} to get user: {}",
+                app_name, info.user
+            ))
+        }
+    }
+
+    match db.quiz_share(&info.uuid, &info.user) {
+        Ok(_) => HttpResponse::Ok().body(format!("{} Shared with {}", info.uuid, info.user)),
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failed to share {} in app {}", info.uuid, app_name)),
+    }
+}
+
+#[delete("/api/v1/quiz/{uuid}/share/{user}")]
+pub async fn delete_quiz_share(
+    data: web::Data<AppState>,
+    info: web::Path<SharePath>,
+    auth: Auth,
+) -> impl Responder {
+    let app_name = &data.app_name;
+    let db = &data.database;
+
+    let access = match find_access(&data, &info.uuid) {
+        Ok(access) => access,
+        Err(response) => return response,
+    };
+    if let Err(response) = auth.require(Action::EditQuiz(&access)) {
+        return response;
+    }
+
+    match db.quiz_unshare(&info.uuid, &info.user) {
+        Ok(_) => HttpResponse::Ok().body(format!("{} Unshared with {}", info.uuid, info.user)),
+        Err(_) => HttpResponse::BadRequest().body(format!(
+            "failed to unshare {} in app {}",
+            info.uuid, app_name
+        )),
+    }
+}
+
+fn find_challenge(data: &AppState, uuid: &String) -> Result<Challenge, HttpResponse> {
+    match data.database.challenge_get(uuid) {
+        Ok(Some(challenge)) => Ok(challenge),
+        Ok(None) => {
+            Err(HttpResponse::NotFound().body(format!("could not find challenge: {}", uuid)))
+        }
+        Err(_) => Err(HttpResponse::BadRequest().body(format!(
+            "failure in {} to get challenge: {}",
+            data.app_name, uuid
+        ))),
+    }
+}
+
+fn find_attempt(data: &AppState, path: &AttemptPath) -> Result<Attempt, HttpResponse> {
+    match data.database.attempt_get(&path.attempt) {
+        Ok(Some(attempt)) if attempt.challenge == path.uuid => Ok(attempt),
+        Ok(_) => {
+            Err(HttpResponse::NotFound().body(format!("could not find attempt: {}", path.attempt)))
+        }
+        Err(_) => Err(HttpResponse::BadRequest().body(format!(
+            "failure in {} to get attempt: {}",
+            data.app_name, path.attempt
+        ))),
+    }
+}
+
+#[post("/api/v1/challenge")]
+pub async fn post_challenge(
+    data: web::Data<AppState>,
+    new: web::Json<NewChallenge>,
+    auth: Auth,
+) -> impl Responder {
+    if let Err(response) = auth.require(Action::HostGame) {
+        return response;
+    }
+    let challenge = match Challenge::create(&new, challenge::now()) {
+        Ok(challenge) => challenge,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+
+    let info = challenge.info(&new.quiz);
+    match data.database.challenge_add(challenge) {
+        Ok(_) => HttpResponse::Ok().json(info),
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failure in {} to add challenge", data.app_name)),
+    }
+}
+
+#[get("/api/v1/challenge/{uuid}")]
+pub async fn get_challenge(
+    data: web::Data<AppState>,
+    info: web::Path<ChallengePath>,
+) -> impl Responder {
+    let challenge = match find_challenge(&data, &info.uuid) {
+        Ok(challenge) => challenge,
+        Err(response) => return response,
+    };
+    match challenge.quiz() {
+        Ok(quiz) => HttpResponse::Ok().json(challenge.info(&quiz)),
+        Err(error) => HttpResponse::InternalServerError().body(error),
+    }
+}
+
+#[post("/api/v1/challenge/{uuid}/attempt")]
+pub async fn post_attempt(
+    data: web::Data<AppState>,
+    info: web::Path<ChallengePath>,
+    new: web::Json<NewAttempt>,
+) -> impl Responder {
+    let challenge = match find_challenge(&data, &info.uuid) {
+        Ok(challenge) => challenge,
+        Err(response) => return response,
+    };
+    let now = challenge::now();
+    if !challenge.is_open(now) {
+        return HttpResponse::BadRequest().body(format!("challenge has closed: {}", info.uuid));
+    }
+    let name = match data.filter.check(&new.name) {
+        Ok(name) => name,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+    let quiz = match challenge.quiz() {
+        Ok(quiz) => quiz,
+        Err(error) => return HttpResponse::InternalServerError().body(error),
+    };
+
+    match data.database.attempt_list(&challenge.uuid) {
+        Ok(attempts) if attempts.iter().any(|attempt| attempt.name == name) => {
+            return HttpResponse::BadRequest().body(format!("nickname already taken: {}", name))
+        }
+        Ok(_) => (),
+        Err(_) => {
+            return HttpResponse::BadRequest()
+                .body(format!("failure in {} to add attempt", data.app_name))
+        }
+    }
+
+    let attempt = Attempt::new(&challenge, &name, now);
+    let response = attempt.info(&quiz);
+    match data.database.attempt_add(attempt) {
+        Ok(_) => HttpResponse::Ok().json(response),
+        Err(_) => {
+            HttpResponse::BadRequest().body(format!("failure in {} to add attempt", data.app_name))
+        }
+    }
+}
+
+#[get("/api/v1/challenge/{uuid}/attempt/{attempt}")]
+pub async fn get_attempt(
+    data: web::Data<AppState>,
+    info: web::Path<AttemptPath>,
+) -> impl Responder {
+    let challenge = match find_challenge(&data, &info.uuid) {
+        Ok(challenge) => challenge,
+        Err(response) => return response,
+    };
+    let attempt = match find_attempt(&data, &info) {
+        Ok(attempt) => attempt,
+        Err(response) => return response,
+    };
+    match challenge.quiz() {
+        Ok(quiz) => HttpResponse::Ok().json(attempt.info(&quiz)),
+        Err(error) => HttpResponse::InternalServerError().body(error),
+    }
+}
+
+#[post("/api/v1/challenge/{uuid}/attempt/{attempt}/answer")]
+pub async fn post_answer(
+    data: web::Data<AppState>,
+    info: web::Path<AttemptPath>,
+    answer: web::Json<AnswerInfo>,
+) -> impl Responder {
+    let challenge = match find_challenge(&data, &info.uuid) {
+        Ok(challenge) => challenge,
+        Err(response) => return response,
+    };
+    let mut attempt = match find_attempt(&data, &info) {
+        Ok(attempt) => attempt,
+        Err(response) => return response,
+    };
+    let now = challenge::now();
+    if !challenge.is_open(now) {
+        return HttpResponse::BadRequest().body(format!("challenge has closed: {}", info.uuid));
+    }
+    let (quiz, settings) = match (challenge.quiz(), challenge.settings()) {
+        (Ok(quiz), Ok(settings)) => (quiz, settings),
+        (Err(error), _) | (_, Err(error)) => {
+            return HttpResponse::InternalServerError().body(error)
+        }
+    };
+
+    let result = match attempt.answer(&quiz, &settings, answer.question, answer.option, now) {
+        Ok(result) => result,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+    let response = Answered {
+        result,
+        attempt: attempt.info(&quiz),
+    };
+    match data.database.attempt_update(attempt) {
+        Ok(_) => HttpResponse::Ok().json(response),
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failure in {} to update attempt", data.app_name)),
+    }
+}
+
+#[get("/api/v1/challenge/{uuid}/leaderboard")]
+pub async fn get_challenge_leaderboard(
+    data: web::Data<AppState>,
+    info: web::Path<ChallengePath>,
+) -> impl Responder {
+    let challenge = match find_challenge(&data, &info.uuid) {
+        Ok(challenge) => challenge,
+        Err(response) => return response,
+    };
+    match data.database.attempt_list(&challenge.uuid) {
+        Ok(attempts) => {
+            let players: Vec<Player> = attempts.iter().map(Attempt::player).collect();
+            HttpResponse::Ok().json(leaderboard::rank(&players, &HashMap::new()))
+        }
+        Err(_) => HttpResponse::BadRequest()
+            .body(format!("failure in {} to list attempts", data.app_name)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use actix::Actor;
     use actix_web::{test, App};
 
     use super::*;
+    use crate::mailer::tests::MemoryMailer;
+    use crate::permissions::Role;
+
+    // signs a user in without the login route, which has its own test
+    fn login(state: &AppState, uuid: &str, role: Role) -> String {
+        let user = User {
+            uuid: uuid.to_string(),
+            username: uuid.to_string(),
+            password: "a long enough password".to_string(),
+            email: format!("{}@email.com", uuid),
+            role,
+            verified: true,
+        };
+        state.database.user_delete(&user.uuid).unwrap();
+        state.database.user_add(user.clone()).unwrap();
+
+        let (token, login) = Login::create(&user, challenge::now());
+        state.database.login_add(&login).unwrap();
+        format!("Bearer {}", token)
+    }
+
+    #[actix_web::test]
+    async fn test_get_ws() {
+        dotenv::dotenv().ok();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Server::new().start()))
+                .app_data(web::Data::new(AppState::new()))
+                .service(get_ws),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/api/v1/ws")
+            .insert_header(("connection", "upgrade"))
+            .insert_header(("upgrade", "websocket"))
+            .insert_header(("sec-websocket-version", "13"))
+            .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::SWITCHING_PROTOCOLS,
+            "status code {:?}",
+            resp.status()
+        );
+    }
 
     #[actix_web::test]
     async fn test_get_health() {
@@ -236,4 +1142,700 @@ mod tests {
             resp.response()
         );
     }
+
+    #[actix_web::test]
+    async fn test_challenge_flow() {
+        dotenv::dotenv().ok();
+
+        let state = AppState::new();
+        let teacher = login(&state, "challenge-teacher", Role::Teacher);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new()))
+                .service(post_challenge)
+                .service(get_challenge)
+                .service(post_attempt)
+                .service(get_attempt)
+                .service(post_answer)
+                .service(get_challenge_leaderboard),
+        )
+        .await;
+        let quiz = serde_json::json!({
+            "uuid": "quiz",
+            "name": "Capitals",
+            "description": "",
+            "questions": [{
+                "question": "Capital of France?",
+                "body": "",
+                "answers": [
+                    {"option": 0, "text": "Paris", "correct": true},
+                    {"option": 1, "text": "Lyon", "correct": false}
+                ]
+            }]
+        });
+        let deadline = challenge::now() + 60_000;
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/challenge")
+            .set_json(serde_json::json!({"quiz": quiz, "deadline": deadline}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        let mut invalid = quiz.clone();
+        invalid["questions"][0]["answers"] = serde_json::json!([]);
+        let req = test::TestRequest::post()
+            .uri("/api/v1/challenge")
+            .insert_header(("authorization", teacher.as_str()))
+            .set_json(serde_json::json!({"quiz": invalid, "deadline": deadline}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/challenge")
+            .insert_header(("authorization", teacher.as_str()))
+            .set_json(serde_json::json!({"quiz": quiz, "deadline": deadline}))
+            .to_request();
+        let created: challenge::ChallengeInfo = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(created.questions, 1);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/v1/challenge/{}/attempt", created.uuid))
+            .set_json(serde_json::json!({"name": "Dave"}))
+            .to_request();
+        let attempt: challenge::AttemptInfo = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(attempt.question.as_ref().unwrap().question, 0);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/v1/challenge/{}/attempt", created.uuid))
+            .set_json(serde_json::json!({"name": "Dave"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/v1/challenge/{}/attempt/{}/answer",
+                created.uuid, attempt.uuid
+            ))
+            .set_json(serde_json::json!({"question": 0, "option": 0}))
+            .to_request();
+        let answered: Answered = test::call_and_read_body_json(&app, req).await;
+        assert!(answered.result.correct);
+        assert!(answered.attempt.finished);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/challenge/{}/leaderboard", created.uuid))
+            .to_request();
+        let standings: Vec<leaderboard::Standing> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(standings.len(), 1);
+        assert_eq!(standings[0].name, "Dave");
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/challenge/missing")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        state.database.challenge_delete(&created.uuid).unwrap();
+        state
+            .database
+            .user_delete(&"challenge-teacher".to_string())
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_quiz_crud() {
+        dotenv::dotenv().ok();
+
+        let state = AppState::new();
+        let teacher = login(&state, "quiz-teacher", Role::Teacher);
+        let colleague = login(&state, "quiz-colleague", Role::Teacher);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new()))
+                .service(list_quiz)
+                .service(get_quiz)
+                .service(post_quiz)
+                .service(put_quiz)
+                .service(patch_quiz)
+                .service(delete_quiz)
+                .service(put_quiz_share),
+        )
+        .await;
+        let mut quiz = serde_json::json!({
+            "uuid": "",
+            "name": "Capitals",
+            "description": "",
+            "questions": [{
+                "question": "Capital of France?",
+                "body": "",
+                "answers": [
+                    {"option": 0, "text": "Paris", "correct": true},
+                    {"option": 1, "text": "Lyon", "correct": false}
+                ]
+            }]
+        });
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/quiz")
+            .insert_header(("authorization", teacher.as_str()))
+            .set_json(&quiz)
+            .to_request();
+        let created: Quiz = test::call_and_read_body_json(&app, req).await;
+        assert!(!created.uuid.is_empty());
+        assert_eq!(created.questions[0].time_limit, 20);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/quiz/{}", created.uuid))
+            .insert_header(("authorization", teacher.as_str()))
+            .to_request();
+        let fetched: Quiz = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(fetched, created);
+
+        quiz["uuid"] = serde_json::json!(created.uuid);
+        quiz["questions"][0]["answers"][0]["correct"] = serde_json::json!(false);
+        let req = test::TestRequest::put()
+            .uri("/api/v1/quiz")
+            .insert_header(("authorization", teacher.as_str()))
+            .set_json(&quiz)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v1/quiz")
+            .insert_header(("authorization", teacher.as_str()))
+            .set_json(serde_json::json!({"uuid": created.uuid, "name": "Capital cities"}))
+            .to_request();
+        let patched: Quiz = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(patched.name, "Capital cities");
+        assert_eq!(patched.questions, created.questions);
+
+        let uri = format!("/api/v1/quiz/{}", created.uuid);
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("authorization", colleague.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::put()
+            .uri(&format!("{}/share/quiz-colleague", uri))
+            .insert_header(("authorization", teacher.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("authorization", colleague.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let req = test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(("authorization", colleague.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/api/v1/quiz/{}", created.uuid))
+            .insert_header(("authorization", teacher.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/quiz/{}", created.uuid))
+            .insert_header(("authorization", teacher.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        state
+            .database
+            .user_delete(&"quiz-teacher".to_string())
+            .unwrap();
+        state
+            .database
+            .user_delete(&"quiz-colleague".to_string())
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_login_flow() {
+        dotenv::dotenv().ok();
+
+        let state = AppState::new();
+        let user = User {
+            uuid: "login".to_string(),
+            username: "Lena".to_string(),
+            password: "a long enough password".to_string(),
+            email: "lena@email.com".to_string(),
+            role: Role::Player,
+            verified: true,
+        };
+        state.database.user_add(user.clone()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new()))
+                .service(post_login)
+                .service(post_logout)
+                .service(get_user),
+        )
+        .await;
+        let uri = format!("/api/v1/user/{}", user.uuid);
+
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/login")
+            .set_json(serde_json::json!({"username": "Lena", "password": "wrong"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/login")
+            .set_json(serde_json::json!({"username": "Lena", "password": user.password}))
+            .to_request();
+        let login: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(login["user"].get("password").is_none());
+        let bearer = format!("Bearer {}", login["token"].as_str().unwrap());
+
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("authorization", bearer.as_str()))
+            .to_request();
+        let fetched: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(fetched["username"], "Lena");
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/logout")
+            .insert_header(("authorization", bearer.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("authorization", bearer.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        state.database.user_delete(&user.uuid).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_user_permissions() {
+        dotenv::dotenv().ok();
+
+        let state = AppState::new();
+        let player = login(&state, "permission-player", Role::Player);
+        let admin = login(&state, "permission-admin", Role::Admin);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState::new()))
+                .service(list_user)
+                .service(get_user)
+                .service(post_user)
+                .service(patch_user)
+                .service(post_quiz),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/user")
+            .insert_header(("authorization", player.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/user/permission-admin")
+            .insert_header(("authorization", player.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/quiz")
+            .insert_header(("authorization", player.as_str()))
+            .set_json(Quiz::default())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        // raw users are for admins only, everyone else goes through register
+        let uuid = "permission-created".to_string();
+        state.database.user_delete(&uuid).unwrap();
+        let body = serde_json::json!({
+            "uuid": uuid,
+            "username": uuid,
+            "password": "a long enough password",
+            "email": "created@email.com",
+            "role": "Teacher"
+        });
+        let req = test::TestRequest::post()
+            .uri("/api/v1/user")
+            .set_json(&body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/user")
+            .insert_header(("authorization", player.as_str()))
+            .set_json(&body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/user")
+            .insert_header(("authorization", admin.as_str()))
+            .set_json(&body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/user/{}", uuid))
+            .insert_header(("authorization", admin.as_str()))
+            .to_request();
+        let created: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(created["role"], "Teacher");
+        assert_eq!(created["verified"], true);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/user")
+            .insert_header(("authorization", admin.as_str()))
+            .set_json(serde_json::json!({
+                "uuid": "permission-clash",
+                "username": "Permission-Player",
+                "password": "a long enough password",
+                "email": "clash@email.com"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::CONFLICT);
+        assert_eq!(test::read_body(resp).await, "username is already taken");
+
+        // leaving verified out of an admin's patch doesn't unconfirm the account
+        let req = test::TestRequest::patch()
+            .uri("/api/v1/user")
+            .insert_header(("authorization", admin.as_str()))
+            .set_json(serde_json::json!({
+                "uuid": "permission-player",
+                "username": "permission-player",
+                "password": "a long enough password",
+                "email": "permission-player@email.com"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let req = test::TestRequest::get()
+            .uri("/api/v1/user/permission-player")
+            .insert_header(("authorization", admin.as_str()))
+            .to_request();
+        let patched: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(patched["verified"], true);
+
+        // an edit without a password keeps the stored one
+        let hash = state
+            .database
+            .user_get(&"permission-player".to_string())
+            .unwrap()
+            .password;
+        let req = test::TestRequest::patch()
+            .uri("/api/v1/user")
+            .insert_header(("authorization", admin.as_str()))
+            .set_json(serde_json::json!({
+                "uuid": "permission-player",
+                "username": "permission-player",
+                "email": "permission-player@email.com"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let stored = state
+            .database
+            .user_get(&"permission-player".to_string())
+            .unwrap();
+        assert_eq!(stored.password, hash);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/user")
+            .insert_header(("authorization", admin.as_str()))
+            .set_json(serde_json::json!({
+                "uuid": "permission-nopassword",
+                "username": "permission-nopassword",
+                "email": "nopassword@email.com"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        for uuid in [uuid.as_str(), "permission-player", "permission-admin"] {
+            state.database.user_delete(&uuid.to_string()).unwrap();
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_register_flow() {
+        dotenv::dotenv().ok();
+
+        let mailer = MemoryMailer::default();
+        let state = AppState {
+            mailer: Box::new(mailer.clone()),
+            ..AppState::new()
+        };
+        let database = state.database.clone();
+        if let Ok(Some(user)) = database.user_verify("register-dave", "correct horse") {
+            database.user_delete(&user.uuid).unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(post_register)
+                .service(get_verify)
+                .service(post_login)
+                .service(get_user)
+                .service(patch_user)
+                .service(post_quiz),
+        )
+        .await;
+
+        let registration = serde_json::json!({
+            "username": "register-dave",
+            "email": "Register-Dave@Email.com",
+            "password": "correct horse"
+        });
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/register")
+            .set_json(serde_json::json!({
+                "username": "register-dave",
+                "email": "register-dave@email.com",
+                "password": "short"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/register")
+            .set_json(&registration)
+            .to_request();
+        let user: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let uuid = user["uuid"].as_str().unwrap().to_string();
+        assert_eq!(user["email"], "register-dave@email.com");
+        assert_eq!(user["role"], "Player");
+        assert_eq!(user["verified"], false);
+        assert!(user.get("password").is_none());
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/register")
+            .set_json(serde_json::json!({
+                "username": "REGISTER-DAVE",
+                "email": "someone@email.com",
+                "password": "correct horse"
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::CONFLICT);
+        assert_eq!(test::read_body(resp).await, "username is already taken");
+
+        // unverified accounts can log in and see themselves but nothing else
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/login")
+            .set_json(Credentials {
+                username: "register-dave".to_string(),
+                password: "correct horse".to_string(),
+            })
+            .to_request();
+        let info: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let bearer = format!("Bearer {}", info["token"].as_str().unwrap());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/user/{}", uuid))
+            .insert_header(("authorization", bearer.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        // a new address waits until a link sent to it is opened, so it can't be used to confirm
+        // an address the user doesn't own
+        let patch = |username: &str, email: &str| {
+            test::TestRequest::patch()
+                .uri("/api/v1/user")
+                .insert_header(("authorization", bearer.as_str()))
+                .set_json(serde_json::json!({
+                    "uuid": uuid,
+                    "username": username,
+                    "password": "correct horse",
+                    "email": email
+                }))
+                .to_request()
+        };
+        let resp =
+            test::call_service(&app, patch("register dave", "register-dave@email.com")).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, patch("register-dave", "not an email")).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let resp =
+            test::call_service(&app, patch("register-dave", "Register-Victim@email.com")).await;
+        assert!(resp.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/user/{}", uuid))
+            .insert_header(("authorization", bearer.as_str()))
+            .to_request();
+        let user: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(user["email"], "register-dave@email.com");
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/auth/verify?token=not-a-token")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let sent = mailer.sent.lock().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].to, "register-dave@email.com");
+        assert_eq!(sent[1].to, "register-victim@email.com");
+        let link = |mail: &Mail| {
+            let line = mail
+                .body
+                .lines()
+                .find(|line| line.contains("/api/v1/auth/verify?token="))
+                .unwrap();
+            line[line.find("/api/v1/auth/verify").unwrap()..].to_string()
+        };
+        let (path, change) = (link(&sent[0]), link(&sent[1]));
+
+        let req = test::TestRequest::get().uri(&path).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        // links are single use
+        let req = test::TestRequest::get().uri(&path).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/user/{}", uuid))
+            .insert_header(("authorization", bearer.as_str()))
+            .to_request();
+        let user: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(user["verified"], true);
+        assert_eq!(user["email"], "register-dave@email.com");
+
+        // confirming the account drops the pending change along with any other links
+        let req = test::TestRequest::get().uri(&change).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        database.user_delete(&uuid).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_password_reset_flow() {
+        dotenv::dotenv().ok();
+
+        let mailer = MemoryMailer::default();
+        let state = AppState {
+            mailer: Box::new(mailer.clone()),
+            detach: false,
+            ..AppState::new()
+        };
+        let database = state.database.clone();
+        let bearer = login(&state, "reset-teacher", Role::Teacher);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(post_forgot)
+                .service(post_reset)
+                .service(post_login)
+                .service(get_user),
+        )
+        .await;
+
+        // unknown addresses get the same answer and no mail
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/forgot")
+            .set_json(ResetRequest {
+                email: "nobody-reset@email.com".to_string(),
+            })
+            .to_request();
+        let unknown = test::call_and_read_body(&app, req).await;
+        assert!(mailer.sent.lock().unwrap().is_empty());
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/forgot")
+            .set_json(ResetRequest {
+                email: "Reset-Teacher@email.com".to_string(),
+            })
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, unknown);
+        let mail = mailer.sent.lock().unwrap().pop().unwrap();
+        assert_eq!(mail.to, "reset-teacher@email.com");
+        let token = mail
+            .body
+            .lines()
+            .find(|line| line.len() == 43)
+            .unwrap()
+            .to_string();
+
+        let change = |password: &str| PasswordChange {
+            token: token.clone(),
+            password: password.to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/reset")
+            .set_json(change("short"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/reset")
+            .set_json(change("a brand new password"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        // the code is single use and every existing login has ended
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/reset")
+            .set_json(change("another new password"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get()
+            .uri("/api/v1/user/reset-teacher")
+            .insert_header(("authorization", bearer.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v1/auth/login")
+            .set_json(Credentials {
+                username: "reset-teacher".to_string(),
+                password: "a brand new password".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        database.user_delete(&"reset-teacher".to_string()).unwrap();
+    }
 }
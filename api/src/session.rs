@@ -0,0 +1,270 @@
+use actix::{
+    fut, Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, ContextFutureSpawner, Handler,
+    Running, StreamHandler, WrapFuture,
+};
+use actix_web::web;
+use actix_web_actors::ws;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::auth;
+use crate::challenge;
+use crate::db::Database;
+use crate::models::{Quiz, Session};
+use crate::permissions::{self, Action};
+use crate::protocol::{ClientEvent, ServerEvent};
+use crate::ws::{ClientMessage, Connect, Disconnect, Server};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// browsers can't set headers on a websocket, so hosts send their login token with the game.
+// a stored quiz is always hosted as it is in the database, not as the client sent it
+fn host_quiz(database: &Database, quiz: Quiz, login: Option<String>) -> Result<Quiz, String> {
+    let user = match login {
+        Some(token) => database
+            .login_user(&auth::token_hash(&token), challenge::now())
+            .map_err(|error| error.to_string())?,
+        None => None,
+    };
+    let user = match user {
+        Some(user) => user,
+        None => return Err("log in to host a game".to_string()),
+    };
+
+    let access = match quiz.uuid.is_empty() {
+        true => None,
+        false => database
+            .quiz_access(&quiz.uuid)
+            .map_err(|error| error.to_string())?,
+    };
+    let quiz = match access {
+        Some(access) if permissions::allowed(&user, Action::HostQuiz(&access)) => database
+            .quiz_get(&quiz.uuid)
+            .map_err(|error| error.to_string())?
+            .ok_or_else(|| format!("could not find quiz: {}", quiz.uuid))?,
+        Some(_) => return Err(format!("not allowed to host quiz: {}", quiz.uuid)),
+        None if permissions::allowed(&user, Action::HostGame) => quiz,
+        None => return Err("not allowed to host games".to_string()),
+    };
+    // an inline quiz has to follow the same rules as a stored one
+    quiz.validate()?;
+    Ok(quiz)
+}
+
+pub struct WsSession {
+    pub session: Session,
+    pub hb: Instant,
+    pub server: Addr<Server>,
+    pub ip: Option<IpAddr>,
+    pub database: Database,
+}
+
+impl WsSession {
+    pub fn new(server: Addr<Server>, ip: Option<IpAddr>, database: Database) -> Self {
+        WsSession {
+            session: Session {
+                uuid: Uuid::new_v4(),
+            },
+            hb: Instant::now(),
+            server,
+            ip,
+            database,
+        }
+    }
+
+    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+                act.server.do_send(Disconnect {
+                    session: act.session,
+                });
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping(b"");
+        });
+    }
+
+    fn send(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &ServerEvent) {
+        match serde_json::to_string(msg) {
+            Ok(text) => ctx.text(text),
+            Err(error) => log::error!("failed to serialize message: {}", error),
+        }
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.hb(ctx);
+
+        let addr = ctx.address();
+        self.server
+            .send(Connect {
+                id: self.session,
+                addr: addr.recipient(),
+                ip: self.ip,
+            })
+            .into_actor(self)
+            .then(|res, _act, ctx| {
+                if res.is_err() {
+                    ctx.stop();
+                }
+                fut::ready(())
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _ctx: &mut Self::Context) -> Running {
+        self.server.do_send(Disconnect {
+            session: self.session,
+        });
+        Running::Stop
+    }
+}
+
+impl Handler<ServerEvent> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: ServerEvent, ctx: &mut Self::Context) -> Self::Result {
+        self.send(ctx, &msg);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(_) => {
+                ctx.stop();
+                return;
+            }
+        };
+
+        match msg {
+            ws::Message::Ping(msg) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            ws::Message::Pong(_) => {
+                self.hb = Instant::now();
+            }
+            ws::Message::Text(text) => match serde_json::from_str::<ClientEvent>(&text) {
+                Ok(ClientEvent::HostCreateGame {
+                    quiz,
+                    settings,
+                    login,
+                }) => {
+                    // the lookups run on the blocking pool, wait keeps later frames behind them
+                    let database = self.database.clone();
+                    web::block(move || host_quiz(&database, quiz, login))
+                        .into_actor(self)
+                        .then(move |res, act, ctx| {
+                            match res {
+                                Ok(Ok(quiz)) => act.server.do_send(ClientMessage {
+                                    session: act.session,
+                                    event: ClientEvent::HostCreateGame {
+                                        quiz,
+                                        settings,
+                                        login: None,
+                                    },
+                                }),
+                                Ok(Err(error)) => act.send(ctx, &ServerEvent::error(&error)),
+                                Err(error) => {
+                                    log::error!("failed to look up hosted quiz: {}", error);
+                                    act.send(ctx, &ServerEvent::error("could not host the game"));
+                                }
+                            }
+                            fut::ready(())
+                        })
+                        .wait(ctx);
+                }
+                Ok(event) => self.server.do_send(ClientMessage {
+                    session: self.session,
+                    event,
+                }),
+                // a malformed frame shouldn't cost the client its connection
+                Err(error) => self.send(
+                    ctx,
+                    &ServerEvent::error(&format!("invalid message: {}", error)),
+                ),
+            },
+            ws::Message::Binary(_) => self.send(
+                ctx,
+                &ServerEvent::error("binary messages are not supported"),
+            ),
+            ws::Message::Close(reason) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            ws::Message::Continuation(_) => {
+                ctx.stop();
+            }
+            ws::Message::Nop => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Answer, Login, Question, User};
+    use crate::permissions::Role;
+
+    fn quiz(answers: usize) -> Quiz {
+        Quiz {
+            uuid: String::new(),
+            name: "Capitals".to_string(),
+            description: String::new(),
+            questions: vec![Question {
+                question: "Capital of France?".to_string(),
+                body: String::new(),
+                answers: (0..answers as i8)
+                    .map(|option| Answer {
+                        option,
+                        text: format!("answer {}", option),
+                        correct: option == 0,
+                    })
+                    .collect(),
+                time_limit: Question::DEFAULT_TIME_LIMIT,
+                points: Question::DEFAULT_POINTS,
+                double_points: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_host_quiz() {
+        dotenv::dotenv().ok();
+
+        let database = Database::new();
+        let user = User {
+            uuid: "session-teacher".to_string(),
+            username: "session-teacher".to_string(),
+            password: "a long enough password".to_string(),
+            email: "session-teacher@email.com".to_string(),
+            role: Role::Teacher,
+            verified: true,
+        };
+        database.user_delete(&user.uuid).unwrap();
+        database.user_add(user.clone()).unwrap();
+        let (token, login) = Login::create(&user, challenge::now());
+        database.login_add(&login).unwrap();
+
+        assert_eq!(
+            host_quiz(&database, quiz(2), None).unwrap_err(),
+            "log in to host a game"
+        );
+        assert_eq!(
+            host_quiz(&database, quiz(1), Some(token.clone())).unwrap_err(),
+            "question 1 needs at least two answers"
+        );
+        assert_eq!(host_quiz(&database, quiz(2), Some(token)).unwrap(), quiz(2));
+
+        database.user_delete(&user.uuid).unwrap();
+    }
+}
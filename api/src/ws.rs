@@ -1,45 +1,51 @@
-use actix::{Actor, Context, Handler, MessageResult, Recipient};
-use actix_web::{web, App, Error, HttpRequest, HttpServer, Responder};
-use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use actix::clock::Instant;
+use actix::dev::SendError;
+use actix::{Actor, AsyncContext, Context, Handler, Recipient};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::models::{Room, Session};
+use crate::game::{Command, Phase, Settings, TeamAssignment};
+use crate::leaderboard;
+use crate::models::{Quiz, Room, Session};
+use crate::nickname::NameFilter;
+use crate::pin::Pins;
+use crate::protocol::{ClientEvent, QuestionInfo, RoomInfo, ServerEvent, PROTOCOL_VERSION};
+use crate::scoring::{self, Submission};
 
-#[derive(Serialize, Deserialize)]
-pub enum MessageType {
-    Create,
-    Join,
-    Leave,
-    Message,
-    Error,
-    Information,
-}
-
-#[derive(Serialize, actix::Message)]
-#[rtype(result = "()")]
-struct Message {
-    msg: String,
-    msg_type: MessageType,
-}
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(actix::Message)]
-#[rtype(result = "Uuid")]
+#[rtype(result = "Result<RoomInfo, String>")]
 pub struct CreateRoom {
     pub session: Session,
+    pub quiz: Quiz,
+    pub settings: Settings,
 }
+
 #[derive(actix::Message)]
-#[rtype(result = "Result<(), String>")]
+#[rtype(result = "Result<RoomInfo, String>")]
 pub struct JoinRoom {
     pub session: Session,
-    pub room: Room,
+    pub pin: u32,
+    pub name: String,
+    pub team: Option<String>,
+}
+
+#[derive(actix::Message)]
+#[rtype(result = "Result<RoomInfo, String>")]
+pub struct SpectateRoom {
+    pub session: Session,
+    pub pin: u32,
 }
 
 #[derive(actix::Message)]
 #[rtype(result = "()")]
 pub struct Connect {
     pub id: Session,
-    pub addr: Recipient<Message>,
+    pub addr: Recipient<ServerEvent>,
+    pub ip: Option<IpAddr>,
 }
 
 #[derive(actix::Message)]
@@ -54,46 +60,793 @@ pub struct Leave {
     pub session: Session,
 }
 
-struct Server {
-    sessions: HashMap<Session, Recipient<Message>>,
-    rooms: HashMap<Room, HashSet<Session>>,
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct ClientMessage {
+    pub session: Session,
+    pub event: ClientEvent,
+}
+
+#[derive(Default)]
+pub struct Server {
+    sessions: HashMap<Session, Recipient<ServerEvent>>,
+    addrs: HashMap<Session, IpAddr>,
+    rooms: HashMap<Uuid, Room>,
+    pins: Pins,
+    filter: NameFilter,
 }
 
 impl Server {
     pub fn new() -> Self {
         Server {
             sessions: HashMap::new(),
+            addrs: HashMap::new(),
             rooms: HashMap::new(),
+            pins: Pins::new(),
+            filter: NameFilter::from_env(),
         }
     }
 
-    pub fn is_empty(&self, room: &Room) -> bool {
+    pub fn is_empty(&self, room: &Uuid) -> bool {
         self.rooms
-            .get(&room)
-            .map(|sessions| sessions.is_empty())
+            .get(room)
+            .map(|room| room.is_empty())
             .unwrap_or(false)
     }
 
-    pub fn send_message(&self, room: &Room, msg: &Message, skip: &Session) {}
+    pub fn send_message(&mut self, room: &Uuid, msg: &ServerEvent, skip: &Session) -> usize {
+        let sessions = match self.rooms.get(room) {
+            Some(room) => room.sessions.clone(),
+            None => return 0,
+        };
+
+        let mut delivered = 0;
+        let mut gone: Vec<Session> = Vec::new();
+        for session in sessions.iter().filter(|session| *session != skip) {
+            match self.sessions.get(session) {
+                Some(addr) => match addr.try_send(msg.clone()) {
+                    Ok(_) => delivered += 1,
+                    Err(SendError::Full(_)) => log::warn!(
+                        "failed to deliver message to {} in room {}: mailbox full",
+                        session.uuid,
+                        room
+                    ),
+                    Err(SendError::Closed(_)) => gone.push(*session),
+                },
+                None => gone.push(*session),
+            }
+        }
+
+        // recipients that have gone away will never receive anything again
+        for session in gone {
+            log::warn!(
+                "failed to deliver message to {} in room {}: session closed",
+                session.uuid,
+                room
+            );
+            self.sessions.remove(&session);
+            if let Some(room) = self.rooms.get_mut(room) {
+                room.sessions.remove(&session);
+            }
+        }
+
+        delivered
+    }
+
+    pub fn send_direct(&self, session: &Session, msg: ServerEvent) {
+        if let Some(addr) = self.sessions.get(session) {
+            addr.do_send(msg);
+        }
+    }
+
+    fn reply_command(&mut self, session: &Session, command: Command, ctx: &mut Context<Self>) {
+        // successful transitions are broadcast to the whole room, including the host
+        if let Err(error) = self.host_command(session, command, ctx) {
+            self.send_direct(session, ServerEvent::error(&error));
+        }
+    }
+
+    pub fn find_room_by_pin(&self, pin: u32) -> Option<Uuid> {
+        self.pins
+            .get(pin)
+            .filter(|uuid| self.rooms.contains_key(uuid))
+    }
+
+    pub fn close_room(&mut self, room: &Uuid) {
+        if let Some(room) = self.rooms.remove(room) {
+            self.pins.release(room.pin);
+        }
+    }
+
+    pub fn session_rooms(&self, session: &Session) -> Vec<Uuid> {
+        self.rooms
+            .values()
+            .filter(|room| room.sessions.contains(session))
+            .map(|room| room.uuid)
+            .collect()
+    }
 
     pub fn leave_rooms(&mut self, session: &Session) {
-        let mut rooms: Vec<Room> = Vec::new();
-        for (room, sessions) in &mut self.rooms {
-            if sessions.remove(&session) {
-                rooms.push(room.clone().to_owned());
+        for uuid in self.session_rooms(session) {
+            if !self.rooms[&uuid].is_host(session) {
+                continue;
+            }
+            let room = self.rooms.get_mut(&uuid).unwrap();
+            room.host = None;
+            match room.connected_cohost() {
+                Some(cohost) => self.hand_off(&uuid, cohost),
+                None if !room.players.is_empty() => {
+                    self.close_game(&uuid, "the host ended the game")
+                }
+                None => (),
             }
         }
 
-        for room in rooms {
-            let msg = Message {
-                msg: "Someone disconncted".to_string(),
-                msg_type: MessageType::Leave,
-            };
-            self.send_message(&room, &msg, &session);
+        let mut rooms: Vec<(Uuid, Option<String>, bool)> = Vec::new();
+        for room in self.rooms.values_mut() {
+            let name = room.names.get(session).cloned();
+            let spectator = room.spectators.contains(session);
+            if room.remove_session(session) || name.is_some() {
+                rooms.push((room.uuid, name, spectator));
+            }
+        }
+
+        for (room, name, spectator) in rooms {
+            // spectators come and go without the room being told
+            if !spectator {
+                let msg = ServerEvent::PlayerLeft { name };
+                self.send_message(&room, &msg, session);
+            }
             if self.is_empty(&room) {
-                self.rooms.remove(&room);
+                self.close_room(&room);
+            }
+        }
+    }
+
+    pub fn broadcast(&mut self, room: &Uuid, msg: &ServerEvent) -> usize {
+        // no connection ever holds the nil session, so nobody is skipped
+        self.send_message(room, msg, &Session { uuid: Uuid::nil() })
+    }
+
+    pub fn hosted_room(&self, session: &Session) -> Option<Uuid> {
+        self.rooms
+            .values()
+            .find(|room| room.can_control(session))
+            .map(|room| room.uuid)
+    }
+
+    pub fn player_room(&self, session: &Session) -> Option<Uuid> {
+        self.rooms
+            .values()
+            .find(|room| room.names.contains_key(session))
+            .map(|room| room.uuid)
+    }
+
+    pub fn host_command(
+        &mut self,
+        session: &Session,
+        command: Command,
+        ctx: &mut Context<Self>,
+    ) -> Result<Phase, String> {
+        let room = match self.hosted_room(session) {
+            Some(room) => room,
+            None => return Err("you are not hosting a game".to_string()),
+        };
+        if self.rooms[&room].paused.is_some() {
+            return Err("game is paused".to_string());
+        }
+        self.advance(&room, command, ctx)
+    }
+
+    pub fn close_game(&mut self, uuid: &Uuid, reason: &str) {
+        let msg = ServerEvent::GameClosed {
+            reason: reason.to_string(),
+        };
+        self.broadcast(uuid, &msg);
+        self.close_room(uuid);
+    }
+
+    pub fn hand_off(&mut self, uuid: &Uuid, session: Session) {
+        let room = match self.rooms.get_mut(uuid) {
+            Some(room) => room,
+            None => return,
+        };
+        let name = room.names.get(&session).cloned();
+        if let Some(name) = &name {
+            room.cohosts.remove(name);
+        }
+        room.host = Some(session);
+
+        self.broadcast(uuid, &ServerEvent::HostChanged { name });
+        self.broadcast_cohosts(uuid);
+    }
+
+    // hands the room to a connected co-host, otherwise the game waits for the host to come back
+    pub fn release_host(&mut self, session: &Session, ctx: &mut Context<Self>) {
+        let rooms: Vec<Uuid> = self
+            .rooms
+            .values()
+            .filter(|room| room.is_host(session))
+            .map(|room| room.uuid)
+            .collect();
+
+        for uuid in rooms {
+            let room = self.rooms.get_mut(&uuid).unwrap();
+            room.host = None;
+            room.sessions.remove(session);
+            if let Some(cohost) = room.connected_cohost() {
+                self.hand_off(&uuid, cohost);
+                continue;
+            }
+            if room.players.is_empty() {
+                self.close_room(&uuid);
+                continue;
+            }
+
+            let now = Instant::now();
+            let grace = Duration::from_secs(room.settings.reconnect_grace);
+            self.pause(&uuid, now);
+            ctx.run_later(grace, move |act, _ctx| act.expire_host(&uuid, now));
+        }
+    }
+
+    fn expire_host(&mut self, uuid: &Uuid, since: Instant) {
+        let waiting = self
+            .rooms
+            .get(uuid)
+            .map(|room| room.host.is_none() && room.paused == Some(since))
+            .unwrap_or(false);
+        if waiting {
+            self.close_game(uuid, "the host did not come back");
+        }
+    }
+
+    pub fn pause(&mut self, uuid: &Uuid, now: Instant) {
+        let room = match self.rooms.get_mut(uuid) {
+            Some(room) => room,
+            None => return,
+        };
+        room.paused = Some(now);
+        // the countdown stops with the deadline and picks up from the same point on resume
+        room.remaining = room
+            .deadline
+            .take()
+            .map(|deadline| deadline.saturating_duration_since(now));
+        self.broadcast(uuid, &ServerEvent::GamePaused);
+    }
+
+    pub fn unpause(&mut self, uuid: &Uuid, ctx: &mut Context<Self>) {
+        let room = match self.rooms.get_mut(uuid) {
+            Some(room) => room,
+            None => return,
+        };
+        let since = match room.paused.take() {
+            Some(since) => since,
+            None => return,
+        };
+
+        let now = Instant::now();
+        if let (Phase::QuestionOpen { question }, Some(remaining)) =
+            (room.phase, room.remaining.take())
+        {
+            let deadline = now + remaining;
+            room.opened = room.opened.map(|opened| opened + (now - since));
+            room.deadline = Some(deadline);
+            self.schedule_tick(*uuid, question, deadline, ctx);
+        }
+        self.broadcast(uuid, &ServerEvent::GameResumed);
+    }
+
+    pub fn resume_host(
+        &mut self,
+        session: &Session,
+        token: &Uuid,
+        ctx: &mut Context<Self>,
+    ) -> Result<RoomInfo, String> {
+        let uuid = match self.rooms.values().find(|room| room.host_token == *token) {
+            Some(room) => room.uuid,
+            None => return Err("host token is unknown or the game has closed".to_string()),
+        };
+
+        self.leave_rooms(session);
+        let room = match self.rooms.get_mut(&uuid) {
+            Some(room) => room,
+            None => return Err("host token is unknown or the game has closed".to_string()),
+        };
+        // whoever stood in for the host stays on as a co-host
+        if let Some(previous) = room.host.take() {
+            match room.names.get(&previous) {
+                Some(name) => {
+                    room.cohosts.insert(name.clone());
+                }
+                None => {
+                    room.sessions.remove(&previous);
+                }
+            }
+        }
+        room.sessions.insert(*session);
+        room.host = Some(*session);
+        let info = RoomInfo::new(room, Some(*token));
+
+        self.send_message(&uuid, &ServerEvent::HostChanged { name: None }, session);
+        self.broadcast_cohosts(&uuid);
+        self.unpause(&uuid, ctx);
+        Ok(info)
+    }
+
+    pub fn kick(&mut self, session: &Session, name: &str, ban: bool) -> Result<Uuid, String> {
+        let uuid = match self.hosted_room(session) {
+            Some(uuid) => uuid,
+            None => return Err("you are not hosting a game".to_string()),
+        };
+        let room = self.rooms.get_mut(&uuid).unwrap();
+        if !room.players.iter().any(|player| player.name == name) {
+            return Err(format!("could not find player: {}", name));
+        }
+
+        let kicked = room.player_session(name);
+        if let Some(kicked) = &kicked {
+            if room.is_host(kicked) {
+                return Err("cannot kick the host".to_string());
+            }
+            room.names.remove(kicked);
+            room.sessions.remove(kicked);
+        }
+        if ban {
+            room.ban_player(name);
+        }
+        room.remove_player(name);
+
+        if let Some(kicked) = &kicked {
+            let reason = if ban {
+                "banned by the host"
+            } else {
+                "removed by the host"
+            };
+            let msg = ServerEvent::Kicked {
+                reason: reason.to_string(),
+            };
+            self.send_direct(kicked, msg);
+        }
+        let msg = ServerEvent::PlayerLeft {
+            name: Some(name.to_string()),
+        };
+        self.broadcast(&uuid, &msg);
+        Ok(uuid)
+    }
+
+    pub fn rename(&mut self, session: &Session, name: &str, to: &str) -> Result<(), String> {
+        let uuid = match self.hosted_room(session) {
+            Some(uuid) => uuid,
+            None => return Err("you are not hosting a game".to_string()),
+        };
+        let to = self.filter.check(to)?;
+        let room = self.rooms.get_mut(&uuid).unwrap();
+        if !room.players.iter().any(|player| player.name == name) {
+            return Err(format!("could not find player: {}", name));
+        }
+        if room.players.iter().any(|player| player.name == to) {
+            return Err(format!("nickname already taken: {}", to));
+        }
+
+        room.rename_player(name, &to);
+        let msg = ServerEvent::PlayerRenamed {
+            from: name.to_string(),
+            to,
+        };
+        self.broadcast(&uuid, &msg);
+        Ok(())
+    }
+
+    pub fn choose_team(&mut self, session: &Session, team: &str) -> Result<(), String> {
+        let room = match self
+            .player_room(session)
+            .and_then(|uuid| self.rooms.get_mut(&uuid))
+        {
+            Some(room) => room,
+            None => return Err("you are not playing a game".to_string()),
+        };
+        if room.settings.team_assignment != TeamAssignment::Chosen {
+            return Err("teams are assigned automatically".to_string());
+        }
+        if room.phase != Phase::Lobby {
+            return Err("teams can only be changed before the game starts".to_string());
+        }
+
+        let team = match room.pick_team(Some(team))? {
+            Some(team) => team,
+            None => return Err("this game is not played in teams".to_string()),
+        };
+        let name = room.names[session].clone();
+        for player in room.players.iter_mut().filter(|player| player.name == name) {
+            player.team = Some(team.clone());
+        }
+        let uuid = room.uuid;
+        self.broadcast(&uuid, &ServerEvent::TeamChanged { name, team });
+        Ok(())
+    }
+
+    pub fn set_cohost(
+        &mut self,
+        session: &Session,
+        name: &str,
+        cohost: bool,
+    ) -> Result<(), String> {
+        let room = match self.rooms.values_mut().find(|room| room.is_host(session)) {
+            Some(room) => room,
+            None => return Err("only the host can change co-hosts".to_string()),
+        };
+        if !room.players.iter().any(|player| player.name == name) {
+            return Err(format!("could not find player: {}", name));
+        }
+
+        if cohost {
+            room.cohosts.insert(name.to_string());
+        } else {
+            room.cohosts.remove(name);
+        }
+        let uuid = room.uuid;
+        self.broadcast_cohosts(&uuid);
+        Ok(())
+    }
+
+    fn broadcast_cohosts(&mut self, uuid: &Uuid) {
+        let mut names: Vec<String> = match self.rooms.get(uuid) {
+            Some(room) => room.cohosts.iter().cloned().collect(),
+            None => return,
+        };
+        names.sort();
+        self.broadcast(uuid, &ServerEvent::CohostsChanged { names });
+    }
+
+    pub fn advance(
+        &mut self,
+        uuid: &Uuid,
+        command: Command,
+        ctx: &mut Context<Self>,
+    ) -> Result<Phase, String> {
+        let room = match self.rooms.get_mut(uuid) {
+            Some(room) => room,
+            None => return Err(format!("could not find room: {}", uuid)),
+        };
+
+        let phase = room.phase.apply(command, room.quiz.questions.len())?;
+        room.phase = phase;
+        room.deadline = None;
+        match phase {
+            Phase::QuestionOpen { question } => {
+                room.answers.clear();
+                room.results.clear();
+                if let Some(current) = room.question() {
+                    let limit = Duration::from_secs(current.time_limit as u64);
+                    let now = Instant::now();
+                    room.opened = Some(now);
+                    room.deadline = Some(now + limit);
+                    self.schedule_tick(*uuid, question, now + limit, ctx);
+                }
+            }
+            // reveal is only ever entered from an open question, so every question is scored once
+            Phase::Reveal { question } => {
+                if let Some(current) = room.quiz.questions.get(question) {
+                    room.results = scoring::score_question(
+                        current,
+                        &room.settings,
+                        &mut room.players,
+                        &room.answers,
+                    );
+                }
             }
+            Phase::Scoreboard { .. } | Phase::Podium => {
+                room.standings = leaderboard::rank(&room.players, &room.ranks);
+                room.ranks = leaderboard::ranks(&room.standings);
+                if room.is_team_game() {
+                    room.team_standings = leaderboard::rank_teams(
+                        &room.players,
+                        &room.settings.teams,
+                        room.settings.team_score,
+                    );
+                }
+            }
+            _ => (),
+        }
+
+        self.broadcast_phase(uuid);
+        Ok(phase)
+    }
+
+    fn schedule_tick(
+        &self,
+        uuid: Uuid,
+        question: usize,
+        deadline: Instant,
+        ctx: &mut Context<Self>,
+    ) {
+        let delay = TICK_INTERVAL.min(deadline.saturating_duration_since(Instant::now()));
+        ctx.run_later(delay, move |act, ctx| {
+            act.tick(uuid, question, deadline, ctx)
+        });
+    }
+
+    // each tick only reschedules itself while its own question is still open with the same
+    // deadline, pausing clears the deadline so a resumed countdown never runs twice
+    fn tick(&mut self, uuid: Uuid, question: usize, deadline: Instant, ctx: &mut Context<Self>) {
+        match self.rooms.get(&uuid) {
+            Some(room)
+                if room.phase == (Phase::QuestionOpen { question })
+                    && room.deadline == Some(deadline) => {}
+            _ => return,
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            if let Err(error) = self.advance(&uuid, Command::Next, ctx) {
+                log::error!("failed to close question in room {}: {}", uuid, error);
+            }
+            return;
+        }
+
+        let msg = ServerEvent::Tick {
+            question,
+            remaining: remaining.as_secs_f32().ceil() as u32,
+        };
+        self.broadcast(&uuid, &msg);
+        self.schedule_tick(uuid, question, deadline, ctx);
+    }
+
+    pub fn close_if_answered(&mut self, uuid: &Uuid, ctx: &mut Context<Self>) {
+        let answered = match self.rooms.get(uuid) {
+            Some(room) => {
+                matches!(room.phase, Phase::QuestionOpen { .. })
+                    && room.paused.is_none()
+                    && !room.names.is_empty()
+                    && room
+                        .names
+                        .values()
+                        .all(|name| room.answers.contains_key(name))
+            }
+            None => false,
+        };
+
+        if answered {
+            if let Err(error) = self.advance(uuid, Command::Next, ctx) {
+                log::error!("failed to close question in room {}: {}", uuid, error);
+            }
+        }
+    }
+
+    fn phase_events(room: &Room) -> Vec<ServerEvent> {
+        let mut events = vec![ServerEvent::PhaseChanged(room.phase)];
+        match (room.phase, room.question()) {
+            (Phase::QuestionOpen { question }, Some(current)) => {
+                let total = room.quiz.questions.len();
+                events.push(ServerEvent::QuestionShown(QuestionInfo::new(
+                    question, total, current,
+                )));
+            }
+            (Phase::Reveal { question }, Some(current)) => {
+                let correct = current
+                    .answers
+                    .iter()
+                    .filter(|answer| answer.correct)
+                    .map(|answer| answer.option)
+                    .collect();
+                events.push(ServerEvent::AnswerRevealed {
+                    question,
+                    correct,
+                    results: room.results.clone(),
+                });
+            }
+            _ => (),
+        }
+        events
+    }
+
+    pub fn broadcast_phase(&mut self, uuid: &Uuid) {
+        let room = match self.rooms.get(uuid) {
+            Some(room) => room,
+            None => return,
+        };
+
+        let phase = room.phase;
+        for event in Self::phase_events(room) {
+            self.broadcast(uuid, &event);
+        }
+
+        if let Phase::Scoreboard { .. } | Phase::Podium = phase {
+            self.send_leaderboard(uuid);
+        }
+    }
+
+    // brings a resumed connection up to date without disturbing anyone else in the room
+    pub fn replay_phase(&self, uuid: &Uuid, session: &Session) {
+        let room = match self.rooms.get(uuid) {
+            Some(room) => room,
+            None => return,
+        };
+
+        for event in Self::phase_events(room) {
+            self.send_direct(session, event);
+        }
+        if let (Phase::QuestionOpen { question }, Some(name)) =
+            (room.phase, room.names.get(session))
+        {
+            if room.answers.contains_key(name) {
+                self.send_direct(session, ServerEvent::AnswerAccepted { question });
+            }
+        }
+        if let Phase::Scoreboard { .. } | Phase::Podium = room.phase {
+            self.send_direct(session, Self::leaderboard(room, session));
+            if room.is_team_game() {
+                self.send_direct(session, Self::team_leaderboard(room));
+            }
+        }
+    }
+
+    fn leaderboard(room: &Room, session: &Session) -> ServerEvent {
+        let top = room
+            .standings
+            .iter()
+            .take(room.settings.leaderboard_size)
+            .cloned()
+            .collect();
+        let you = room.names.get(session).and_then(|name| {
+            room.standings
+                .iter()
+                .find(|standing| &standing.name == name)
+                .cloned()
+        });
+        ServerEvent::Leaderboard {
+            question: room.phase.question(),
+            top,
+            you,
+        }
+    }
+
+    fn team_leaderboard(room: &Room) -> ServerEvent {
+        ServerEvent::TeamLeaderboard {
+            question: room.phase.question(),
+            teams: room.team_standings.clone(),
+        }
+    }
+
+    // the host gets the top of the table, players additionally get their own standing
+    pub fn send_leaderboard(&self, uuid: &Uuid) {
+        let room = match self.rooms.get(uuid) {
+            Some(room) => room,
+            None => return,
+        };
+
+        for session in &room.sessions {
+            self.send_direct(session, Self::leaderboard(room, session));
+            if room.is_team_game() {
+                self.send_direct(session, Self::team_leaderboard(room));
+            }
+        }
+    }
+
+    pub fn hold_rooms(&mut self, session: &Session, ctx: &mut Context<Self>) {
+        let now = Instant::now();
+        let mut held: Vec<(Uuid, String, Duration)> = Vec::new();
+        for room in self.rooms.values_mut() {
+            if let Some(name) = room.hold_session(session, now) {
+                let grace = Duration::from_secs(room.settings.reconnect_grace);
+                held.push((room.uuid, name, grace));
+            }
+        }
+
+        for (uuid, name, grace) in held {
+            let msg = ServerEvent::PlayerDisconnected { name: name.clone() };
+            self.broadcast(&uuid, &msg);
+            ctx.run_later(grace, move |act, _ctx| act.expire_hold(&uuid, &name, now));
+        }
+    }
+
+    fn expire_hold(&mut self, uuid: &Uuid, name: &str, since: Instant) {
+        let room = match self.rooms.get_mut(uuid) {
+            Some(room) => room,
+            None => return,
+        };
+        // the player either came back or dropped again later, which started a fresh hold
+        if room.held.get(name) != Some(&since) {
+            return;
+        }
+
+        room.remove_player(name);
+        let msg = ServerEvent::PlayerLeft {
+            name: Some(name.to_string()),
+        };
+        self.broadcast(uuid, &msg);
+        if self.is_empty(uuid) && self.rooms[uuid].held.is_empty() {
+            self.close_room(uuid);
+        }
+    }
+
+    pub fn resume(
+        &mut self,
+        session: &Session,
+        token: &Uuid,
+        ctx: &mut Context<Self>,
+    ) -> Result<RoomInfo, String> {
+        let uuid = match self
+            .rooms
+            .values()
+            .find(|room| room.tokens.contains_key(token) || room.banned_tokens.contains(token))
+        {
+            Some(room) => room.uuid,
+            None => return Err("reconnect token is unknown or has expired".to_string()),
+        };
+
+        self.leave_rooms(session);
+        let room = match self.rooms.get_mut(&uuid) {
+            Some(room) => room,
+            None => return Err("reconnect token is unknown or has expired".to_string()),
+        };
+        if room.banned_tokens.contains(token) {
+            return Err("you have been banned from this game".to_string());
+        }
+        let name = match room.resume_session(*session, token) {
+            Some(name) => name,
+            None => return Err("reconnect token is unknown or has expired".to_string()),
+        };
+        let info = RoomInfo::new(room, Some(*token));
+        let takes_over = room.host.is_none() && room.cohosts.contains(&name);
+
+        let msg = ServerEvent::PlayerResumed { name };
+        self.send_message(&uuid, &msg, session);
+        if takes_over {
+            self.hand_off(&uuid, *session);
+            self.unpause(&uuid, ctx);
+        }
+        Ok(info)
+    }
+
+    pub fn submit_answer(
+        &mut self,
+        session: &Session,
+        question: usize,
+        option: i8,
+    ) -> Result<Uuid, String> {
+        let room = match self
+            .player_room(session)
+            .and_then(|uuid| self.rooms.get_mut(&uuid))
+        {
+            Some(room) => room,
+            None => return Err("you are not playing a game".to_string()),
+        };
+
+        if room.phase != (Phase::QuestionOpen { question }) {
+            return Err(format!("question {} is not open", question));
+        }
+        if room.paused.is_some() {
+            return Err("game is paused".to_string());
+        }
+        let valid = room
+            .question()
+            .map(|current| current.answers.iter().any(|answer| answer.option == option))
+            .unwrap_or(false);
+        if !valid {
+            return Err(format!("invalid option: {}", option));
         }
+
+        let now = Instant::now();
+        if room
+            .deadline
+            .map(|deadline| now > deadline)
+            .unwrap_or(false)
+        {
+            return Err(format!("question {} is closed", question));
+        }
+
+        let name = room.names[session].clone();
+        if room.answers.contains_key(&name) {
+            return Err(format!("already answered question {}", question));
+        }
+        let elapsed = room
+            .opened
+            .map(|opened| now.saturating_duration_since(opened))
+            .unwrap_or_default();
+        room.answers.insert(name, Submission { option, elapsed });
+        Ok(room.uuid)
     }
 }
 
@@ -105,20 +858,31 @@ impl Handler<Connect> for Server {
     type Result = ();
 
     fn handle(&mut self, msg: Connect, _ctx: &mut Self::Context) -> Self::Result {
-        let Connect { id, addr } = msg;
+        let Connect { id, addr, ip } = msg;
+        if let Some(ip) = ip {
+            self.addrs.insert(id, ip);
+        }
+        addr.do_send(ServerEvent::Welcome {
+            version: PROTOCOL_VERSION,
+            session: id.uuid,
+        });
         self.sessions.insert(id, addr);
     }
 }
+
 impl Handler<Disconnect> for Server {
     type Result = ();
 
     fn handle(
         &mut self,
         Disconnect { session }: Disconnect,
-        _ctx: &mut Self::Context,
+        ctx: &mut Self::Context,
     ) -> Self::Result {
+        self.release_host(&session, ctx);
+        self.hold_rooms(&session, ctx);
         self.leave_rooms(&session);
         let _ = self.sessions.remove(&session);
+        let _ = self.addrs.remove(&session);
     }
 }
 
@@ -130,8 +894,1127 @@ impl Handler<Leave> for Server {
     }
 }
 
-impl Handler<Message> for Server {
+impl Handler<CreateRoom> for Server {
+    type Result = Result<RoomInfo, String>;
+
+    fn handle(
+        &mut self,
+        CreateRoom {
+            session,
+            quiz,
+            settings,
+        }: CreateRoom,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        settings.validate()?;
+        let uuid = Uuid::new_v4();
+        let pin = match self.pins.issue(uuid) {
+            Some(pin) => pin,
+            None => return Err("no game pins available".to_string()),
+        };
+        let mut room = Room::new(uuid, pin, quiz, settings);
+        room.sessions.insert(session);
+        room.host = Some(session);
+        let info = RoomInfo::new(&room, Some(room.host_token));
+
+        self.leave_rooms(&session);
+        self.rooms.insert(uuid, room);
+        Ok(info)
+    }
+}
+
+impl Handler<JoinRoom> for Server {
+    type Result = Result<RoomInfo, String>;
+
+    fn handle(
+        &mut self,
+        JoinRoom {
+            session,
+            pin,
+            name,
+            team,
+        }: JoinRoom,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let name = self.filter.check(&name)?;
+        let name = name.as_str();
+
+        let uuid = match self.find_room_by_pin(pin) {
+            Some(uuid) => uuid,
+            None => return Err(format!("could not find game: {}", pin)),
+        };
+        let room = &self.rooms[&uuid];
+        if room.phase != Phase::Lobby {
+            return Err(format!("game has already started: {}", pin));
+        }
+        if room.players.iter().any(|player| player.name == name) {
+            return Err(format!("nickname already taken: {}", name));
+        }
+        let team = room.pick_team(team.as_deref())?;
+        let addr = self.addrs.get(&session).copied();
+        if addr
+            .map(|addr| room.banned_addrs.contains(&addr))
+            .unwrap_or(false)
+        {
+            return Err("you have been banned from this game".to_string());
+        }
+
+        self.leave_rooms(&session);
+        let msg = ServerEvent::PlayerJoined {
+            name: name.to_string(),
+            team: team.clone(),
+        };
+        self.send_message(&uuid, &msg, &session);
+
+        let room = match self.rooms.get_mut(&uuid) {
+            Some(room) => room,
+            None => return Err(format!("could not find game: {}", pin)),
+        };
+        let token = room.add_player(session, name, team);
+        if let Some(addr) = addr {
+            room.addrs.insert(name.to_string(), addr);
+        }
+        Ok(RoomInfo::new(room, Some(token)))
+    }
+}
+
+impl Handler<SpectateRoom> for Server {
+    type Result = Result<RoomInfo, String>;
+
+    fn handle(
+        &mut self,
+        SpectateRoom { session, pin }: SpectateRoom,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let uuid = match self.find_room_by_pin(pin) {
+            Some(uuid) => uuid,
+            None => return Err(format!("could not find game: {}", pin)),
+        };
+        if !self.rooms[&uuid].settings.allow_spectators {
+            return Err(format!("game does not allow spectators: {}", pin));
+        }
+
+        // spectators may arrive at any point in the game and never count as players
+        self.leave_rooms(&session);
+        let room = match self.rooms.get_mut(&uuid) {
+            Some(room) => room,
+            None => return Err(format!("could not find game: {}", pin)),
+        };
+        room.sessions.insert(session);
+        room.spectators.insert(session);
+        Ok(RoomInfo::new(room, None))
+    }
+}
+
+impl Handler<ClientMessage> for Server {
     type Result = ();
 
-    fn handle(&mut self, msg: Message, _ctx: &mut Self::Context) -> Self::Result {}
+    fn handle(
+        &mut self,
+        ClientMessage { session, event }: ClientMessage,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        match event {
+            ClientEvent::HostCreateGame { quiz, settings, .. } => {
+                let create = CreateRoom {
+                    session,
+                    quiz,
+                    settings,
+                };
+                let reply = match self.handle(create, ctx) {
+                    Ok(info) => ServerEvent::GameCreated(info),
+                    Err(error) => ServerEvent::error(&error),
+                };
+                self.send_direct(&session, reply);
+            }
+            ClientEvent::HostStartGame => self.reply_command(&session, Command::Start, ctx),
+            ClientEvent::HostNext => self.reply_command(&session, Command::Next, ctx),
+            ClientEvent::HostEndGame => self.reply_command(&session, Command::End, ctx),
+            ClientEvent::PlayerJoin { pin, name, team } => {
+                let join = JoinRoom {
+                    session,
+                    pin,
+                    name,
+                    team,
+                };
+                let reply = match self.handle(join, ctx) {
+                    Ok(info) => ServerEvent::GameJoined(info),
+                    Err(error) => ServerEvent::error(&error),
+                };
+                self.send_direct(&session, reply);
+            }
+            ClientEvent::PlayerResume { token } => match self.resume(&session, &token, ctx) {
+                Ok(info) => {
+                    let uuid = info.room;
+                    self.send_direct(&session, ServerEvent::GameJoined(info));
+                    self.replay_phase(&uuid, &session);
+                }
+                Err(error) => self.send_direct(&session, ServerEvent::error(&error)),
+            },
+            ClientEvent::HostResume { token } => match self.resume_host(&session, &token, ctx) {
+                Ok(info) => {
+                    let uuid = info.room;
+                    self.send_direct(&session, ServerEvent::GameCreated(info));
+                    self.replay_phase(&uuid, &session);
+                }
+                Err(error) => self.send_direct(&session, ServerEvent::error(&error)),
+            },
+            ClientEvent::HostKick { name } => match self.kick(&session, &name, false) {
+                Ok(room) => self.close_if_answered(&room, ctx),
+                Err(error) => self.send_direct(&session, ServerEvent::error(&error)),
+            },
+            ClientEvent::HostBan { name } => match self.kick(&session, &name, true) {
+                Ok(room) => self.close_if_answered(&room, ctx),
+                Err(error) => self.send_direct(&session, ServerEvent::error(&error)),
+            },
+            ClientEvent::HostRename { name, to } => {
+                if let Err(error) = self.rename(&session, &name, &to) {
+                    self.send_direct(&session, ServerEvent::error(&error));
+                }
+            }
+            ClientEvent::PlayerChooseTeam { team } => {
+                if let Err(error) = self.choose_team(&session, &team) {
+                    self.send_direct(&session, ServerEvent::error(&error));
+                }
+            }
+            ClientEvent::HostPromote { name } => {
+                if let Err(error) = self.set_cohost(&session, &name, true) {
+                    self.send_direct(&session, ServerEvent::error(&error));
+                }
+            }
+            ClientEvent::HostDemote { name } => {
+                if let Err(error) = self.set_cohost(&session, &name, false) {
+                    self.send_direct(&session, ServerEvent::error(&error));
+                }
+            }
+            ClientEvent::PlayerAnswer { question, option } => {
+                match self.submit_answer(&session, question, option) {
+                    Ok(room) => {
+                        self.send_direct(&session, ServerEvent::AnswerAccepted { question });
+                        self.close_if_answered(&room, ctx);
+                    }
+                    Err(error) => self.send_direct(&session, ServerEvent::error(&error)),
+                }
+            }
+            ClientEvent::SpectatorJoin { pin } => {
+                match self.handle(SpectateRoom { session, pin }, ctx) {
+                    Ok(info) => {
+                        let uuid = info.room;
+                        self.send_direct(&session, ServerEvent::GameJoined(info));
+                        self.replay_phase(&uuid, &session);
+                    }
+                    Err(error) => self.send_direct(&session, ServerEvent::error(&error)),
+                }
+            }
+            ClientEvent::Leave => self.leave_rooms(&session),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Answer, Question};
+    use actix::Addr;
+    use std::sync::{Arc, Mutex};
+
+    struct Collector {
+        received: Arc<Mutex<Vec<ServerEvent>>>,
+    }
+
+    impl Actor for Collector {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<ServerEvent> for Collector {
+        type Result = ();
+
+        fn handle(&mut self, msg: ServerEvent, _ctx: &mut Self::Context) -> Self::Result {
+            self.received.lock().unwrap().push(msg);
+        }
+    }
+
+    fn connect(server: &mut Server) -> (Session, Arc<Mutex<Vec<ServerEvent>>>) {
+        let session = Session {
+            uuid: Uuid::new_v4(),
+        };
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let addr = Collector {
+            received: received.clone(),
+        }
+        .start();
+        server.sessions.insert(session, addr.recipient());
+        (session, received)
+    }
+
+    fn room(server: &mut Server, sessions: &[Session]) -> Uuid {
+        let mut room = Room::new(Uuid::new_v4(), 0, Quiz::default(), Settings::default());
+        room.sessions.extend(sessions.iter().copied());
+        let uuid = room.uuid;
+        server.rooms.insert(uuid, room);
+        uuid
+    }
+
+    #[actix_web::test]
+    async fn test_join_room_by_pin() {
+        let server = Server::new().start();
+        let host = Session {
+            uuid: Uuid::new_v4(),
+        };
+        let player = Session {
+            uuid: Uuid::new_v4(),
+        };
+
+        let info = server
+            .send(CreateRoom {
+                session: host,
+                quiz: Quiz::default(),
+                settings: Settings::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let joined = server
+            .send(JoinRoom {
+                session: player,
+                pin: info.pin,
+                name: "Dave".to_string(),
+                team: None,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((joined.room, joined.pin), (info.room, info.pin));
+        assert!(info.token.is_some());
+        assert_ne!(joined.token, info.token);
+
+        let taken = server
+            .send(JoinRoom {
+                session: Session {
+                    uuid: Uuid::new_v4(),
+                },
+                pin: info.pin,
+                name: "Dave".to_string(),
+                team: None,
+            })
+            .await
+            .unwrap();
+        assert!(taken.is_err());
+
+        let missing = server
+            .send(JoinRoom {
+                session: player,
+                pin: info.pin + 1,
+                name: "Bob".to_string(),
+                team: None,
+            })
+            .await
+            .unwrap();
+        assert!(missing.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_close_room_releases_pin() {
+        let mut server = Server::new();
+        let (host, _) = connect(&mut server);
+        let mut ctx = Context::new();
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: Quiz::default(),
+                    settings: Settings::default(),
+                },
+                &mut ctx,
+            )
+            .unwrap();
+        assert!(server.find_room_by_pin(info.pin).is_some());
+
+        server.leave_rooms(&host);
+        assert!(server.find_room_by_pin(info.pin).is_none());
+        assert!(server.pins.get(info.pin).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_send_message_skips_sender() {
+        let mut server = Server::new();
+        let (alice, alice_received) = connect(&mut server);
+        let (bob, bob_received) = connect(&mut server);
+        let room = room(&mut server, &[alice, bob]);
+
+        let msg = ServerEvent::PlayerLeft {
+            name: Some("carol".to_string()),
+        };
+        assert_eq!(server.send_message(&room, &msg, &alice), 1);
+        actix::clock::sleep(std::time::Duration::from_millis(10)).await;
+
+        assert!(alice_received.lock().unwrap().is_empty());
+        assert_eq!(bob_received.lock().unwrap().len(), 1);
+        assert_eq!(bob_received.lock().unwrap()[0], msg);
+    }
+
+    #[actix_web::test]
+    async fn test_send_message_drops_closed_sessions() {
+        let mut server = Server::new();
+        let (alice, _) = connect(&mut server);
+        let missing = Session {
+            uuid: Uuid::new_v4(),
+        };
+        let room = room(&mut server, &[alice, missing]);
+
+        let msg = ServerEvent::error("hello");
+        assert_eq!(server.send_message(&room, &msg, &alice), 0);
+        assert!(!server.rooms[&room].sessions.contains(&missing));
+        assert!(server.rooms[&room].sessions.contains(&alice));
+    }
+
+    #[actix_web::test]
+    async fn test_room_survives_player_changes() {
+        let mut server = Server::new();
+        let (host, _) = connect(&mut server);
+        let (player, _) = connect(&mut server);
+        let mut ctx = Context::new();
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: Quiz::default(),
+                    settings: Settings::default(),
+                },
+                &mut ctx,
+            )
+            .unwrap();
+        server
+            .handle(
+                JoinRoom {
+                    session: player,
+                    pin: info.pin,
+                    name: "Dave".to_string(),
+                    team: None,
+                },
+                &mut ctx,
+            )
+            .unwrap();
+
+        server.rooms.get_mut(&info.room).unwrap().players[0].points = 100;
+        assert_eq!(server.find_room_by_pin(info.pin), Some(info.room));
+        assert_eq!(server.rooms[&info.room].players[0].points, 100);
+
+        server.leave_rooms(&player);
+        assert!(server.rooms[&info.room].players.is_empty());
+        assert!(server.rooms[&info.room].sessions.contains(&host));
+    }
+
+    fn quiz() -> Quiz {
+        let question = Question {
+            question: "Capital of France?".to_string(),
+            body: String::new(),
+            answers: vec![
+                Answer {
+                    option: 0,
+                    text: "Paris".to_string(),
+                    correct: true,
+                },
+                Answer {
+                    option: 1,
+                    text: "Lyon".to_string(),
+                    correct: false,
+                },
+            ],
+            time_limit: 2,
+            points: 1000,
+            double_points: false,
+        };
+        Quiz {
+            uuid: "quiz".to_string(),
+            name: "Capitals".to_string(),
+            description: String::new(),
+            questions: vec![question.clone(), question],
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_host_drives_game() {
+        let mut server = Server::new();
+        let (host, host_received) = connect(&mut server);
+        let (player, player_received) = connect(&mut server);
+        let mut ctx = Context::new();
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: quiz(),
+                    settings: Settings::default(),
+                },
+                &mut ctx,
+            )
+            .unwrap();
+        server
+            .handle(
+                JoinRoom {
+                    session: player,
+                    pin: info.pin,
+                    name: "Dave".to_string(),
+                    team: None,
+                },
+                &mut ctx,
+            )
+            .unwrap();
+
+        assert!(server
+            .host_command(&player, Command::Start, &mut ctx)
+            .is_err());
+        assert!(server.submit_answer(&player, 0, 0).is_err());
+        assert!(server.host_command(&host, Command::Next, &mut ctx).is_err());
+
+        assert_eq!(
+            server.host_command(&host, Command::Start, &mut ctx),
+            Ok(Phase::QuestionOpen { question: 0 })
+        );
+        assert!(server.submit_answer(&player, 1, 0).is_err());
+        assert!(server.submit_answer(&player, 0, 7).is_err());
+        assert!(server.submit_answer(&player, 0, 0).is_ok());
+        assert!(server.submit_answer(&player, 0, 1).is_err());
+
+        assert_eq!(
+            server.host_command(&host, Command::Next, &mut ctx),
+            Ok(Phase::Reveal { question: 0 })
+        );
+        assert!(server.submit_answer(&player, 0, 0).is_err());
+        assert_eq!(
+            server.host_command(&host, Command::End, &mut ctx),
+            Ok(Phase::Podium)
+        );
+        actix::clock::sleep(std::time::Duration::from_millis(10)).await;
+
+        let received = player_received.lock().unwrap();
+        assert!(
+            received.contains(&ServerEvent::PhaseChanged(Phase::QuestionOpen {
+                question: 0
+            }))
+        );
+        assert!(received.iter().any(|event| matches!(
+            event,
+            ServerEvent::QuestionShown(info) if info.question == 0 && info.answers.len() == 2
+        )));
+        assert!(received.iter().any(|event| matches!(
+            event,
+            ServerEvent::AnswerRevealed { question: 0, correct, results }
+                if correct == &vec![0] && results.len() == 1 && results[0].correct
+        )));
+        assert!(received.contains(&ServerEvent::PhaseChanged(Phase::Podium)));
+        drop(received);
+
+        let player = &server.rooms[&info.room].players[0];
+        assert_eq!((player.correct, player.wrong), (1, 0));
+        assert!(player.points > 500 && player.points <= 1000);
+
+        let received = player_received.lock().unwrap();
+        assert!(received.iter().any(|event| matches!(
+            event,
+            ServerEvent::Leaderboard { top, you: Some(you), .. }
+                if top.len() == 1 && you.rank == 1 && you.name == "Dave"
+        )));
+        let received = host_received.lock().unwrap();
+        assert!(received.iter().any(|event| matches!(
+            event,
+            ServerEvent::Leaderboard { top, you: None, .. } if top.len() == 1
+        )));
+    }
+
+    async fn attach(server: &Addr<Server>) -> (Session, Arc<Mutex<Vec<ServerEvent>>>) {
+        let session = Session {
+            uuid: Uuid::new_v4(),
+        };
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let addr = Collector {
+            received: received.clone(),
+        }
+        .start();
+        server
+            .send(Connect {
+                id: session,
+                addr: addr.recipient(),
+                ip: None,
+            })
+            .await
+            .unwrap();
+        (session, received)
+    }
+
+    async fn game(
+        server: &Addr<Server>,
+        names: &[&str],
+    ) -> (Session, Arc<Mutex<Vec<ServerEvent>>>, Vec<Session>) {
+        let (host, host_received) = attach(server).await;
+        let info = server
+            .send(CreateRoom {
+                session: host,
+                quiz: quiz(),
+                settings: Settings::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut players = Vec::new();
+        for name in names {
+            let (session, _) = attach(server).await;
+            server
+                .send(JoinRoom {
+                    session,
+                    pin: info.pin,
+                    name: name.to_string(),
+                    team: None,
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            players.push(session);
+        }
+        (host, host_received, players)
+    }
+
+    #[actix_web::test]
+    async fn test_question_closes_when_time_expires() {
+        // countdowns run on actix's clock, so paused time skips ahead instead of waiting
+        tokio::time::pause();
+        let server = Server::new().start();
+        let (host, host_received, _) = game(&server, &["Dave"]).await;
+
+        server.do_send(ClientMessage {
+            session: host,
+            event: ClientEvent::HostStartGame,
+        });
+        actix::clock::sleep(Duration::from_millis(2_500)).await;
+
+        let received = host_received.lock().unwrap();
+        assert!(received
+            .iter()
+            .any(|event| matches!(event, ServerEvent::Tick { question: 0, .. })));
+        assert!(received.contains(&ServerEvent::PhaseChanged(Phase::Reveal { question: 0 })));
+    }
+
+    #[actix_web::test]
+    async fn test_question_closes_when_everyone_answered() {
+        let server = Server::new().start();
+        let (host, host_received, players) = game(&server, &["Alice", "Bob"]).await;
+
+        server.do_send(ClientMessage {
+            session: host,
+            event: ClientEvent::HostStartGame,
+        });
+        for session in players {
+            server.do_send(ClientMessage {
+                session,
+                event: ClientEvent::PlayerAnswer {
+                    question: 0,
+                    option: 0,
+                },
+            });
+        }
+        actix::clock::sleep(Duration::from_millis(50)).await;
+
+        let received = host_received.lock().unwrap();
+        assert!(received.contains(&ServerEvent::PhaseChanged(Phase::Reveal { question: 0 })));
+        assert!(!received
+            .iter()
+            .any(|event| matches!(event, ServerEvent::Tick { .. })));
+    }
+
+    #[actix_web::test]
+    async fn test_player_resumes_with_token() {
+        let mut server = Server::new();
+        let (host, host_received) = connect(&mut server);
+        let (player, _) = connect(&mut server);
+        let mut ctx = Context::new();
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: quiz(),
+                    settings: Settings::default(),
+                },
+                &mut ctx,
+            )
+            .unwrap();
+        let joined = server
+            .handle(
+                JoinRoom {
+                    session: player,
+                    pin: info.pin,
+                    name: "Dave".to_string(),
+                    team: None,
+                },
+                &mut ctx,
+            )
+            .unwrap();
+        let token = joined.token.unwrap();
+        server
+            .host_command(&host, Command::Start, &mut ctx)
+            .unwrap();
+        server.submit_answer(&player, 0, 0).unwrap();
+        server.rooms.get_mut(&info.room).unwrap().players[0].points = 100;
+
+        server.handle(Disconnect { session: player }, &mut ctx);
+        let room = &server.rooms[&info.room];
+        assert_eq!(room.players.len(), 1);
+        assert!(room.held.contains_key("Dave"));
+        assert!(!room.sessions.contains(&player));
+
+        let (resumed, resumed_received) = connect(&mut server);
+        assert!(server.resume(&resumed, &Uuid::new_v4(), &mut ctx).is_err());
+        let info = server.resume(&resumed, &token, &mut ctx).unwrap();
+        server.replay_phase(&info.room, &resumed);
+        actix::clock::sleep(Duration::from_millis(10)).await;
+
+        let room = &server.rooms[&info.room];
+        assert_eq!(room.names.get(&resumed), Some(&"Dave".to_string()));
+        assert!(room.held.is_empty());
+        assert_eq!(room.players[0].points, 100);
+
+        let received = resumed_received.lock().unwrap();
+        assert!(
+            received.contains(&ServerEvent::PhaseChanged(Phase::QuestionOpen {
+                question: 0
+            }))
+        );
+        assert!(received.contains(&ServerEvent::AnswerAccepted { question: 0 }));
+        let received = host_received.lock().unwrap();
+        assert!(received.contains(&ServerEvent::PlayerDisconnected {
+            name: "Dave".to_string()
+        }));
+        assert!(received.contains(&ServerEvent::PlayerResumed {
+            name: "Dave".to_string()
+        }));
+    }
+
+    #[actix_web::test]
+    async fn test_held_player_expires() {
+        let mut server = Server::new();
+        let (host, _) = connect(&mut server);
+        let (player, _) = connect(&mut server);
+        let mut ctx = Context::new();
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: quiz(),
+                    settings: Settings::default(),
+                },
+                &mut ctx,
+            )
+            .unwrap();
+        let token = server
+            .handle(
+                JoinRoom {
+                    session: player,
+                    pin: info.pin,
+                    name: "Dave".to_string(),
+                    team: None,
+                },
+                &mut ctx,
+            )
+            .unwrap()
+            .token
+            .unwrap();
+
+        server.handle(Disconnect { session: player }, &mut ctx);
+        let since = server.rooms[&info.room].held["Dave"];
+        server.expire_hold(&info.room, "Dave", since);
+
+        let room = &server.rooms[&info.room];
+        assert!(room.players.is_empty());
+        assert!(room.tokens.is_empty());
+        let (resumed, _) = connect(&mut server);
+        assert!(server.resume(&resumed, &token, &mut ctx).is_err());
+    }
+
+    fn hosted(
+        server: &mut Server,
+        ctx: &mut Context<Server>,
+        names: &[&str],
+    ) -> (RoomInfo, Session, Vec<Session>) {
+        let (host, _) = connect(server);
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: quiz(),
+                    settings: Settings::default(),
+                },
+                ctx,
+            )
+            .unwrap();
+        let mut players = Vec::new();
+        for name in names {
+            let (player, _) = connect(server);
+            server
+                .handle(
+                    JoinRoom {
+                        session: player,
+                        pin: info.pin,
+                        name: name.to_string(),
+                        team: None,
+                    },
+                    ctx,
+                )
+                .unwrap();
+            players.push(player);
+        }
+        (info, host, players)
+    }
+
+    #[actix_web::test]
+    async fn test_host_disconnect_pauses_game() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, host, players) = hosted(&mut server, &mut ctx, &["Dave"]);
+        server
+            .host_command(&host, Command::Start, &mut ctx)
+            .unwrap();
+
+        server.handle(Disconnect { session: host }, &mut ctx);
+        let room = &server.rooms[&info.room];
+        assert!(room.host.is_none());
+        assert!(room.paused.is_some());
+        assert!(room.deadline.is_none());
+        assert!(room.remaining.is_some());
+        assert!(server.submit_answer(&players[0], 0, 0).is_err());
+
+        let (returned, _) = connect(&mut server);
+        assert!(server
+            .resume_host(&returned, &Uuid::new_v4(), &mut ctx)
+            .is_err());
+        server
+            .resume_host(&returned, &info.token.unwrap(), &mut ctx)
+            .unwrap();
+        let room = &server.rooms[&info.room];
+        assert!(room.is_host(&returned));
+        assert!(room.paused.is_none());
+        assert!(room.deadline.is_some());
+        assert!(server.submit_answer(&players[0], 0, 0).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_host_expires_closes_game() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, host, _) = hosted(&mut server, &mut ctx, &["Dave"]);
+
+        server.handle(Disconnect { session: host }, &mut ctx);
+        let since = server.rooms[&info.room].paused.unwrap();
+        server.expire_host(&info.room, since);
+        assert!(!server.rooms.contains_key(&info.room));
+        assert!(server.find_room_by_pin(info.pin).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_cohost_takes_over() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, host, players) = hosted(&mut server, &mut ctx, &["Alice", "Bob"]);
+
+        assert!(server.set_cohost(&players[1], "Alice", true).is_err());
+        server.set_cohost(&host, "Alice", true).unwrap();
+        assert!(server.set_cohost(&players[0], "Bob", true).is_err());
+        assert!(server
+            .host_command(&players[1], Command::Start, &mut ctx)
+            .is_err());
+        assert_eq!(
+            server.host_command(&players[0], Command::Start, &mut ctx),
+            Ok(Phase::QuestionOpen { question: 0 })
+        );
+
+        server.handle(Disconnect { session: host }, &mut ctx);
+        let room = &server.rooms[&info.room];
+        assert!(room.is_host(&players[0]));
+        assert!(room.cohosts.is_empty());
+        assert!(room.paused.is_none());
+
+        let (returned, _) = connect(&mut server);
+        server
+            .resume_host(&returned, &info.token.unwrap(), &mut ctx)
+            .unwrap();
+        let room = &server.rooms[&info.room];
+        assert!(room.is_host(&returned));
+        assert!(room.cohosts.contains("Alice"));
+    }
+
+    #[actix_web::test]
+    async fn test_host_kicks_player() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, host, players) = hosted(&mut server, &mut ctx, &["Alice", "Bob"]);
+
+        assert!(server.kick(&players[0], "Bob", false).is_err());
+        assert!(server.kick(&host, "Eve", false).is_err());
+        assert_eq!(server.kick(&host, "Bob", false), Ok(info.room));
+
+        let room = &server.rooms[&info.room];
+        assert_eq!(room.players.len(), 1);
+        assert!(!room.sessions.contains(&players[1]));
+        assert!(server.player_room(&players[1]).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_host_bans_player() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, host, _) = hosted(&mut server, &mut ctx, &[]);
+        server
+            .rooms
+            .get_mut(&info.room)
+            .unwrap()
+            .settings
+            .ban_addresses = true;
+
+        let (player, _) = connect(&mut server);
+        let addr: IpAddr = "10.0.0.7".parse().unwrap();
+        server.addrs.insert(player, addr);
+        let token = server
+            .handle(
+                JoinRoom {
+                    session: player,
+                    pin: info.pin,
+                    name: "Dave".to_string(),
+                    team: None,
+                },
+                &mut ctx,
+            )
+            .unwrap()
+            .token
+            .unwrap();
+
+        assert_eq!(server.kick(&host, "Dave", true), Ok(info.room));
+        assert_eq!(
+            server.resume(&player, &token, &mut ctx),
+            Err("you have been banned from this game".to_string())
+        );
+        let (again, _) = connect(&mut server);
+        server.addrs.insert(again, addr);
+        let rejoined = server.handle(
+            JoinRoom {
+                session: again,
+                pin: info.pin,
+                name: "Not Dave".to_string(),
+                team: None,
+            },
+            &mut ctx,
+        );
+        assert!(rejoined.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_host_renames_player() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, host, players) = hosted(&mut server, &mut ctx, &["Alice", "Bob"]);
+        server
+            .host_command(&host, Command::Start, &mut ctx)
+            .unwrap();
+        server.submit_answer(&players[0], 0, 0).unwrap();
+
+        assert!(server.rename(&players[1], "Alice", "Carol").is_err());
+        assert!(server.rename(&host, "Alice", "Bob").is_err());
+        assert!(server.rename(&host, "Alice", "sh1t").is_err());
+        server.rename(&host, "Alice", "  Carol ").unwrap();
+
+        let room = &server.rooms[&info.room];
+        assert_eq!(room.names[&players[0]], "Carol");
+        assert!(room.answers.contains_key("Carol"));
+        assert!(room.players.iter().any(|player| player.name == "Carol"));
+        assert!(server.submit_answer(&players[0], 0, 0).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_join_filters_nickname() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, _, _) = hosted(&mut server, &mut ctx, &[]);
+        let (player, _) = connect(&mut server);
+
+        let join = |name: &str| JoinRoom {
+            session: player,
+            pin: info.pin,
+            name: name.to_string(),
+            team: None,
+        };
+        assert!(server.handle(join("F U C K"), &mut ctx).is_err());
+        assert!(server.handle(join("   "), &mut ctx).is_err());
+        server.handle(join(" Dave  Smith "), &mut ctx).unwrap();
+        assert_eq!(server.rooms[&info.room].names[&player], "Dave Smith");
+    }
+
+    #[actix_web::test]
+    async fn test_team_game() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (host, host_received) = connect(&mut server);
+        let settings = Settings {
+            teams: vec!["Red".to_string(), "Blue".to_string()],
+            ..Settings::default()
+        };
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: quiz(),
+                    settings,
+                },
+                &mut ctx,
+            )
+            .unwrap();
+
+        let mut players = Vec::new();
+        for name in ["Alice", "Bob", "Carol"] {
+            let (player, _) = connect(&mut server);
+            let joined = server
+                .handle(
+                    JoinRoom {
+                        session: player,
+                        pin: info.pin,
+                        name: name.to_string(),
+                        team: Some("Blue".to_string()),
+                    },
+                    &mut ctx,
+                )
+                .unwrap();
+            assert_eq!(joined.teams.len(), 2);
+            players.push((player, joined.team.unwrap()));
+        }
+        let teams: Vec<&str> = players.iter().map(|(_, team)| team.as_str()).collect();
+        assert_eq!(teams, vec!["Red", "Blue", "Red"]);
+        assert!(server.choose_team(&players[0].0, "Blue").is_err());
+
+        server
+            .host_command(&host, Command::Start, &mut ctx)
+            .unwrap();
+        server.submit_answer(&players[1].0, 0, 0).unwrap();
+        server.host_command(&host, Command::End, &mut ctx).unwrap();
+        actix::clock::sleep(Duration::from_millis(10)).await;
+
+        let standings = &server.rooms[&info.room].team_standings;
+        assert_eq!(standings[0].team, "Blue");
+        assert_eq!(standings[1].points, 0);
+        let received = host_received.lock().unwrap();
+        assert!(received.iter().any(|event| matches!(
+            event,
+            ServerEvent::TeamLeaderboard { teams, .. } if teams.len() == 2
+        )));
+    }
+
+    #[actix_web::test]
+    async fn test_choose_team() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (host, _) = connect(&mut server);
+        let settings = Settings {
+            teams: vec!["Red".to_string(), "Blue".to_string()],
+            team_assignment: TeamAssignment::Chosen,
+            ..Settings::default()
+        };
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: quiz(),
+                    settings,
+                },
+                &mut ctx,
+            )
+            .unwrap();
+
+        let (player, _) = connect(&mut server);
+        let join = |team: &str| JoinRoom {
+            session: player,
+            pin: info.pin,
+            name: "Dave".to_string(),
+            team: Some(team.to_string()),
+        };
+        assert!(server.handle(join("Green"), &mut ctx).is_err());
+        let joined = server.handle(join("Blue"), &mut ctx).unwrap();
+        assert_eq!(joined.team.as_deref(), Some("Blue"));
+
+        assert!(server.choose_team(&player, "Green").is_err());
+        server.choose_team(&player, "Red").unwrap();
+        let team = &server.rooms[&info.room].players[0].team;
+        assert_eq!(team.as_deref(), Some("Red"));
+
+        server
+            .host_command(&host, Command::Start, &mut ctx)
+            .unwrap();
+        assert!(server.choose_team(&player, "Blue").is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_spectator_watches_game() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (info, host, players) = hosted(&mut server, &mut ctx, &["Dave"]);
+        server
+            .host_command(&host, Command::Start, &mut ctx)
+            .unwrap();
+
+        let (spectator, spectator_received) = connect(&mut server);
+        server.handle(
+            ClientMessage {
+                session: spectator,
+                event: ClientEvent::SpectatorJoin { pin: info.pin },
+            },
+            &mut ctx,
+        );
+        assert!(server.submit_answer(&spectator, 0, 0).is_err());
+        assert!(server
+            .host_command(&spectator, Command::Next, &mut ctx)
+            .is_err());
+        assert_eq!(server.rooms[&info.room].players.len(), 1);
+
+        server.submit_answer(&players[0], 0, 0).unwrap();
+        server.close_if_answered(&info.room, &mut ctx);
+        actix::clock::sleep(Duration::from_millis(10)).await;
+
+        let received = spectator_received.lock().unwrap();
+        assert!(matches!(received[0], ServerEvent::GameJoined(_)));
+        assert!(received
+            .iter()
+            .any(|event| matches!(event, ServerEvent::QuestionShown(_))));
+        assert!(received.contains(&ServerEvent::PhaseChanged(Phase::Reveal { question: 0 })));
+        drop(received);
+
+        server.leave_rooms(&spectator);
+        assert!(!server.rooms[&info.room].sessions.contains(&spectator));
+        assert!(server.rooms[&info.room].spectators.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_spectators_disabled() {
+        let mut server = Server::new();
+        let mut ctx = Context::new();
+        let (host, _) = connect(&mut server);
+        let settings = Settings {
+            allow_spectators: false,
+            ..Settings::default()
+        };
+        let info = server
+            .handle(
+                CreateRoom {
+                    session: host,
+                    quiz: quiz(),
+                    settings,
+                },
+                &mut ctx,
+            )
+            .unwrap();
+
+        let (spectator, _) = connect(&mut server);
+        let spectate = server.handle(
+            SpectateRoom {
+                session: spectator,
+                pin: info.pin,
+            },
+            &mut ctx,
+        );
+        assert!(spectate.is_err());
+    }
 }
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type AnswerResult = { name: string, option: number | null, correct: boolean, points: number, bonus: number, streak: number, total: number, };
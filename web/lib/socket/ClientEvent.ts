@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Quiz } from "./Quiz";
+import type { Settings } from "./Settings";
+
+export type ClientEvent = { "type": "HostCreateGame", quiz: Quiz, settings: Settings, login: string | null, } | { "type": "HostStartGame" } | { "type": "HostNext" } | { "type": "HostEndGame" } | { "type": "PlayerJoin", pin: number, name: string, team: string | null, } | { "type": "SpectatorJoin", pin: number, } | { "type": "PlayerChooseTeam", team: string, } | { "type": "PlayerResume", token: string, } | { "type": "HostResume", token: string, } | { "type": "HostKick", name: string, } | { "type": "HostBan", name: string, } | { "type": "HostRename", name: string, to: string, } | { "type": "HostPromote", name: string, } | { "type": "HostDemote", name: string, } | { "type": "PlayerAnswer", question: number, option: number, } | { "type": "Leave" };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Answer } from "./Answer";
+
+export type Question = { question: string, body: string, answers: Array<Answer>, time_limit: number, points: number, double_points: boolean, };
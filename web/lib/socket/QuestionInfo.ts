@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AnswerOption } from "./AnswerOption";
+
+export type QuestionInfo = { question: number, total: number, text: string, body: string, answers: Array<AnswerOption>, time_limit: number, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Question } from "./Question";
+
+export type Quiz = { uuid: string, name: string, description: string, questions: Array<Question>, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RoomInfo = { room: string, pin: number, quiz: string, token: string | null, teams: Array<string>, team: string | null, };
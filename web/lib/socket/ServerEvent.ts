@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AnswerResult } from "./AnswerResult";
+import type { Phase } from "./Phase";
+import type { QuestionInfo } from "./QuestionInfo";
+import type { RoomInfo } from "./RoomInfo";
+import type { Standing } from "./Standing";
+import type { TeamStanding } from "./TeamStanding";
+
+export type ServerEvent = { "type": "Welcome", version: number, session: string, } | { "type": "GameCreated" } & RoomInfo | { "type": "GameJoined" } & RoomInfo | { "type": "PlayerJoined", name: string, team: string | null, } | { "type": "TeamChanged", name: string, team: string, } | { "type": "PlayerLeft", name: string | null, } | { "type": "PlayerDisconnected", name: string, } | { "type": "PlayerResumed", name: string, } | { "type": "PlayerRenamed", from: string, to: string, } | { "type": "HostChanged", name: string | null, } | { "type": "CohostsChanged", names: Array<string>, } | { "type": "GamePaused" } | { "type": "GameResumed" } | { "type": "GameClosed", reason: string, } | { "type": "Kicked", reason: string, } | { "type": "PhaseChanged" } & Phase | { "type": "QuestionShown" } & QuestionInfo | { "type": "Tick", question: number, remaining: number, } | { "type": "AnswerAccepted", question: number, } | { "type": "AnswerRevealed", question: number, correct: Array<number>, results: Array<AnswerResult>, } | { "type": "Leaderboard", question: number | null, top: Array<Standing>, you: Standing | null, } | { "type": "TeamLeaderboard", question: number | null, teams: Array<TeamStanding>, } | { "type": "Error", message: string, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TeamAssignment } from "./TeamAssignment";
+import type { TeamScore } from "./TeamScore";
+
+export type Settings = { streak_bonus: Array<number>, leaderboard_size: number, reconnect_grace: number, ban_addresses: boolean, teams: Array<string>, team_assignment: TeamAssignment, team_score: TeamScore, allow_spectators: boolean, };